@@ -0,0 +1,210 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Adaptive elision.
+//!
+//! Some critical sections simply do not fit in a hardware
+//! transaction, every attempt ends in a capacity abort and is
+//! pure overhead before the fallback runs anyway. `Adaptive`
+//! keeps a little state per call site and stops attempting the
+//! hardware path after repeated capacity aborts, probing it
+//! again after a while.
+
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use crate::elision::Site;
+use crate::{AbortStatus, Commit, Fallback, RetryPolicy};
+
+/// Capacity aborts in a row before the hardware path is skipped.
+const THRESHOLD: u32 = 3;
+
+/// Calls that skip the hardware path the first time it is
+/// disabled, doubled each time the probe fails again.
+const MIN_SKIP: u32 = 16;
+
+/// Upper bound on the calls skipped in one go.
+const MAX_SKIP: u32 = 4096;
+
+/// Per call site controller around `transaction_retry`.
+///
+/// Meant to be kept in a `static` next to the call site:
+///
+/// ```ignore
+/// static SITE: Adaptive = Adaptive::new();
+///
+/// SITE.transaction_retry(&mut data, |data| update(data), 3, Fallback::Striped)
+/// ```
+///
+/// Without a fallback the hardware path is never skipped, there
+/// would be nothing else to run.
+pub struct Adaptive {
+    threshold: u32,
+    max_skip: u32,
+    streak: AtomicU32,
+    skip: AtomicU32,
+    next_skip: AtomicU32,
+    last: AtomicU32,
+    commits: AtomicUsize,
+    aborts: AtomicUsize,
+    capacity: AtomicUsize,
+    skipped: AtomicUsize,
+}
+
+impl Adaptive {
+    /// Creates a controller with the default limits.
+    pub const fn new() -> Adaptive {
+        Adaptive::with_limits(THRESHOLD, MAX_SKIP)
+    }
+
+    /// Creates a controller which disables the hardware path
+    /// after `threshold` capacity aborts in a row, and skips it
+    /// for at most `max_skip` calls before probing again.
+    pub const fn with_limits(threshold: u32, max_skip: u32) -> Adaptive {
+        Adaptive {
+            threshold,
+            max_skip,
+            streak: AtomicU32::new(0),
+            skip: AtomicU32::new(0),
+            next_skip: AtomicU32::new(MIN_SKIP),
+            last: AtomicU32::new(0),
+            commits: AtomicUsize::new(0),
+            aborts: AtomicUsize::new(0),
+            capacity: AtomicUsize::new(0),
+            skipped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Runs `transaction_retry`, unless this site currently skips
+    /// the hardware path in which case `lambda` runs straight
+    /// under the fallback. The reported status is then the one
+    /// that disabled the hardware path.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn transaction_retry<S, F, P>(
+        &self,
+        data: &mut S,
+        lambda: F,
+        policy: P,
+        fallback: Fallback,
+    ) -> Result<Commit, AbortStatus>
+    where
+        S: Sync,
+        F: Fn(&mut S),
+        P: RetryPolicy,
+    {
+        if let Option::Some(lock) = fallback.lock_for(data as *const S as usize) {
+            if self.take_skip() {
+                self.skipped.fetch_add(1, Ordering::Relaxed);
+                let status = AbortStatus::from_raw(self.last.load(Ordering::Relaxed));
+                Site::caller().fallback(status);
+                let _guard = lock.lock();
+                crate::undo::run(|| lambda(data))?;
+                return Ok(Commit::Fallback(status));
+            }
+        }
+        let outcome = crate::transaction_retry(data, lambda, policy, fallback);
+        match outcome {
+            Ok(Commit::Hardware) => self.committed(),
+            Ok(Commit::Fallback(status)) | Err(status) => self.aborted(status),
+        }
+        outcome
+    }
+
+    /// Returns `true` while the hardware path is being skipped.
+    pub fn is_disabled(&self) -> bool {
+        self.skip.load(Ordering::Relaxed) > 0
+    }
+
+    /// Fraction of the hardware attempts (after retries) that
+    /// committed, `1.0` before any was made.
+    pub fn success_ratio(&self) -> f64 {
+        let commits = self.commits.load(Ordering::Relaxed);
+        let total = commits + self.aborts.load(Ordering::Relaxed);
+        if total == 0 {
+            1.0
+        } else {
+            commits as f64 / total as f64
+        }
+    }
+
+    /// Number of calls whose hardware attempts ended in a
+    /// capacity abort.
+    pub fn capacity_aborts(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Number of calls that went straight to the fallback.
+    pub fn skipped(&self) -> usize {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// Consumes one skipped call if the hardware path is disabled.
+    #[inline]
+    fn take_skip(&self) -> bool {
+        let mut skip = self.skip.load(Ordering::Relaxed);
+        while skip > 0 {
+            match self.skip.compare_exchange_weak(
+                skip,
+                skip - 1,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(current) => skip = current,
+            }
+        }
+        false
+    }
+
+    fn committed(&self) {
+        self.commits.fetch_add(1, Ordering::Relaxed);
+        if self.streak.load(Ordering::Relaxed) != 0 {
+            self.streak.store(0, Ordering::Relaxed);
+        }
+        if self.next_skip.load(Ordering::Relaxed) != MIN_SKIP {
+            self.next_skip.store(MIN_SKIP, Ordering::Relaxed);
+        }
+    }
+
+    fn aborted(&self, status: AbortStatus) {
+        self.aborts.fetch_add(1, Ordering::Relaxed);
+        if !status.capacity() {
+            self.streak.store(0, Ordering::Relaxed);
+            return;
+        }
+        self.capacity.fetch_add(1, Ordering::Relaxed);
+        if self
+            .streak
+            .fetch_add(1, Ordering::Relaxed)
+            .saturating_add(1)
+            < self.threshold
+        {
+            return;
+        }
+        // disable the hardware path, the streak is kept so a single
+        // failed probe disables it again for twice as many calls
+        let skip = self.next_skip.load(Ordering::Relaxed);
+        self.next_skip
+            .store(skip.saturating_mul(2).min(self.max_skip), Ordering::Relaxed);
+        self.last.store(status.raw(), Ordering::Relaxed);
+        self.skip.store(skip.min(self.max_skip), Ordering::Relaxed);
+    }
+}
+
+impl Default for Adaptive {
+    fn default() -> Adaptive {
+        Adaptive::new()
+    }
+}
@@ -0,0 +1,216 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Containers that keep transactional data on its own cache lines.
+//!
+//! RTM tracks reads and writes by cache line, so two values
+//! sharing a line conflict even when no thread touches the
+//! other's value. These types start their value on a line
+//! boundary and round its size up to whole lines.
+
+use alloc::boxed::Box;
+use core::ops::{Deref, DerefMut, Index};
+
+use crate::{TxCell, TxObject};
+
+/// Size of a cache line on every processor with RTM.
+pub const CACHE_LINE: usize = 64;
+
+/// Lines the L1 data cache can hold, a transaction writing more
+/// can never commit.
+pub(crate) const WRITE_SET_LINES: usize = 512;
+
+/// The most cache lines a `T` can touch wherever it is placed.
+///
+/// A value whose alignment is below a line may straddle one more
+/// line than its size suggests.
+///
+/// ```ignore
+/// const _: () = assert!(rtm::cache_lines::<[u64; 8]>() == 2);
+/// const _: () = assert!(rtm::cache_lines::<rtm::CacheAligned<[u64; 8]>>() == 1);
+/// ```
+pub const fn cache_lines<T>() -> usize {
+    let size = core::mem::size_of::<T>();
+    if size == 0 {
+        return 0;
+    }
+    let slack = CACHE_LINE.saturating_sub(core::mem::align_of::<T>());
+    (size + slack).div_ceil(CACHE_LINE)
+}
+
+/// A value starting on a cache line boundary, its size rounded
+/// up to whole lines.
+#[repr(align(64))]
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash, Debug)]
+pub struct CacheAligned<T>(T);
+
+impl<T> CacheAligned<T> {
+    /// Wraps `value`.
+    #[inline]
+    pub const fn new(value: T) -> CacheAligned<T> {
+        CacheAligned(value)
+    }
+
+    /// Returns the wrapped value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+/// A value padded to a pair of cache lines.
+///
+/// The adjacent line prefetcher of Intel cores pulls lines in
+/// pairs, so values one line apart can still abort each other's
+/// transactions. Use this for hot values written from different
+/// threads, such as the head and tail of a queue.
+#[repr(align(128))]
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash, Debug)]
+pub struct CachePadded<T>(T);
+
+impl<T> CachePadded<T> {
+    /// Wraps `value`.
+    #[inline]
+    pub const fn new(value: T) -> CachePadded<T> {
+        CachePadded(value)
+    }
+
+    /// Returns the wrapped value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+macro_rules! wrapper_impls {
+    ($name: ident) => {
+        impl<T> Deref for $name<T> {
+            type Target = T;
+            #[inline]
+            fn deref(&self) -> &T {
+                &self.0
+            }
+        }
+
+        impl<T> DerefMut for $name<T> {
+            #[inline]
+            fn deref_mut(&mut self) -> &mut T {
+                &mut self.0
+            }
+        }
+
+        impl<T> From<T> for $name<T> {
+            #[inline]
+            fn from(value: T) -> $name<T> {
+                $name(value)
+            }
+        }
+
+        impl<T: TxObject> TxObject for $name<T> {
+            #[inline]
+            fn lock_addr(&self) -> usize {
+                self.0.lock_addr()
+            }
+        }
+    };
+}
+wrapper_impls!(CacheAligned);
+wrapper_impls!(CachePadded);
+
+/// A heap allocation starting on a cache line boundary.
+///
+/// `Box` only honours the alignment of its value, so two small
+/// boxes may share a line.
+#[derive(Clone, Default, PartialEq, Eq, Hash, Debug)]
+pub struct TxBox<T>(Box<CacheAligned<T>>);
+
+impl<T> TxBox<T> {
+    /// Moves `value` into a new allocation.
+    #[inline]
+    pub fn new(value: T) -> TxBox<T> {
+        TxBox(Box::new(CacheAligned(value)))
+    }
+
+    /// Moves the value back out of the allocation.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0 .0
+    }
+}
+
+impl<T> Deref for TxBox<T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for TxBox<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: TxObject> TxObject for TxBox<T> {
+    #[inline]
+    fn lock_addr(&self) -> usize {
+        self.0.lock_addr()
+    }
+}
+
+/// A fixed number of `TxCell`s, each on its own cache line.
+///
+/// Transactions on different slots never conflict. Each slot is
+/// a `TxObject`, so several can be updated by `transaction_many`.
+pub struct TxSlots<T: Copy> {
+    slots: Box<[CacheAligned<TxCell<T>>]>,
+}
+
+impl<T: Copy> TxSlots<T> {
+    /// Creates `len` slots all holding `value`.
+    pub fn new(len: usize, value: T) -> TxSlots<T> {
+        TxSlots {
+            slots: (0..len).map(|_| CacheAligned(TxCell::new(value))).collect(),
+        }
+    }
+
+    /// The number of slots.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns `true` if there are no slots.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Iterates over the slots.
+    pub fn iter(&self) -> impl Iterator<Item = &TxCell<T>> + '_ {
+        self.slots.iter().map(|slot| &slot.0)
+    }
+}
+
+impl<T: Copy> Index<usize> for TxSlots<T> {
+    type Output = TxCell<T>;
+    #[inline]
+    fn index(&self, index: usize) -> &TxCell<T> {
+        &self.slots[index].0
+    }
+}
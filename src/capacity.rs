@@ -0,0 +1,282 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Estimates of whether a transaction fits the hardware.
+//!
+//! Written lines are buffered in the L1 data cache, a
+//! transaction whose write set does not fit there (or overflows
+//! one set of it) aborts on capacity every time. Read lines are
+//! tracked further out and so can be more numerous.
+//!
+//! A footprint is either derived from the types a transaction
+//! touches with `TxFootprint`, or measured with a `Trace` taken
+//! from the `emulated` backend, which also knows which sets of
+//! the cache the lines fall in.
+
+use alloc::collections::BTreeSet;
+use core::ops::Add;
+
+use crate::aligned::WRITE_SET_LINES;
+use crate::{cache_lines, CacheAligned, CachePadded, TxBox, TxCell, TxSlots, TxVec, CACHE_LINE};
+
+/// Cache lines a transaction reads and writes.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash, Debug)]
+pub struct Footprint {
+    /// Lines read, written lines may be counted here as well.
+    pub reads: usize,
+
+    /// Lines written.
+    pub writes: usize,
+}
+
+impl Footprint {
+    /// Reading and writing every line a `T` can touch.
+    #[inline]
+    pub const fn of<T>() -> Footprint {
+        Footprint::lines(cache_lines::<T>())
+    }
+
+    /// Reading and writing `lines` lines.
+    #[inline]
+    pub const fn lines(lines: usize) -> Footprint {
+        Footprint {
+            reads: lines,
+            writes: lines,
+        }
+    }
+}
+
+impl Add for Footprint {
+    type Output = Footprint;
+    #[inline]
+    fn add(self, other: Footprint) -> Footprint {
+        Footprint {
+            reads: self.reads + other.reads,
+            writes: self.writes + other.writes,
+        }
+    }
+}
+
+/// Data that reports the cache lines a transaction over it may
+/// touch.
+///
+/// The default is every line of the value itself, which is right
+/// for plain data. Types owning memory elsewhere must add it.
+pub trait TxFootprint {
+    /// The lines a transaction over `self` may read and write.
+    #[inline]
+    fn footprint(&self) -> Footprint
+    where
+        Self: Sized,
+    {
+        Footprint::of::<Self>()
+    }
+}
+
+macro_rules! plain_footprint {
+    ($($name: ty),*) => {
+        $(impl TxFootprint for $name {})*
+    };
+}
+plain_footprint!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool, char
+);
+
+impl<T: Copy> TxFootprint for TxCell<T> {}
+
+/// `Copy` values hold no memory elsewhere.
+impl<T: Copy + TxFootprint, const N: usize> TxFootprint for [T; N] {}
+
+impl<T: Copy> TxFootprint for TxVec<T> {
+    fn footprint(&self) -> Footprint {
+        let slots = self.capacity() * core::mem::size_of::<T>();
+        // the slots may start part way into a line
+        Footprint::lines(cache_lines::<Self>() + slots.div_ceil(CACHE_LINE) + 1)
+    }
+}
+
+impl<T: Copy> TxFootprint for TxSlots<T> {
+    fn footprint(&self) -> Footprint {
+        Footprint::lines(
+            cache_lines::<Self>() + self.len() * cache_lines::<CacheAligned<TxCell<T>>>(),
+        )
+    }
+}
+
+impl<T: TxFootprint> TxFootprint for TxBox<T> {
+    /// The value, and the line holding the pointer to it.
+    fn footprint(&self) -> Footprint {
+        (**self).footprint()
+            + Footprint {
+                reads: 1,
+                writes: 0,
+            }
+    }
+}
+
+impl<T: TxFootprint> TxFootprint for CacheAligned<T> {
+    fn footprint(&self) -> Footprint {
+        (**self).footprint()
+    }
+}
+
+impl<T: TxFootprint> TxFootprint for CachePadded<T> {
+    fn footprint(&self) -> Footprint {
+        (**self).footprint()
+    }
+}
+
+macro_rules! tuple_footprint {
+    ($($name: ident),*) => {
+        impl<$($name: TxFootprint),*> TxFootprint for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn footprint(&self) -> Footprint {
+                let ($($name,)*) = self;
+                Footprint::default() $(+ $name.footprint())*
+            }
+        }
+    };
+}
+tuple_footprint!(A, B);
+tuple_footprint!(A, B, C);
+tuple_footprint!(A, B, C, D);
+
+/// The lines touched by emulated transactions, recorded with
+/// `emulated::record`.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Trace {
+    reads: BTreeSet<usize>,
+    writes: BTreeSet<usize>,
+}
+
+impl Trace {
+    /// Creates an empty trace.
+    pub fn new() -> Trace {
+        Trace::default()
+    }
+
+    /// Records an access to the `len` bytes at `addr`.
+    pub fn touch(&mut self, addr: usize, len: usize, write: bool) {
+        let lines = if write {
+            &mut self.writes
+        } else {
+            &mut self.reads
+        };
+        let first = addr / CACHE_LINE;
+        let last = (addr + len.max(1) - 1) / CACHE_LINE;
+        lines.extend(first..=last);
+    }
+
+    /// The distinct lines read and written.
+    pub fn footprint(&self) -> Footprint {
+        Footprint {
+            reads: self.reads.union(&self.writes).count(),
+            writes: self.writes.len(),
+        }
+    }
+
+    /// The most written lines that fall in a single set of a
+    /// cache with `sets` sets.
+    pub fn busiest_set(&self, sets: usize) -> usize {
+        let mut counts = alloc::vec![0usize; sets.max(1)];
+        for &line in &self.writes {
+            counts[line % sets.max(1)] += 1;
+        }
+        counts.into_iter().max().unwrap_or(0)
+    }
+}
+
+/// How a footprint compares with what the hardware can hold.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Fit {
+    /// Well within both sets.
+    Fits,
+
+    /// Over half of what a set holds. A busy sibling hyperthread
+    /// shares the L1 and can push the transaction over.
+    Tight,
+
+    /// Can never commit in hardware, it always aborts on capacity.
+    Exceeds,
+}
+
+/// The size of the read and write sets of a processor.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Capacity {
+    /// Lines of the L1 data cache.
+    pub write_lines: usize,
+
+    /// Ways of the L1 data cache, a set holds this many lines.
+    pub ways: usize,
+
+    /// Lines that can be read, tracked beyond the L1.
+    pub read_lines: usize,
+}
+
+impl Capacity {
+    /// A 32 KiB 8 way L1, and a read set bounded by a 256 KiB L2,
+    /// which is every core with RTM.
+    pub const L1: Capacity = Capacity {
+        write_lines: WRITE_SET_LINES,
+        ways: 8,
+        read_lines: 4096,
+    };
+
+    /// Estimates whether `footprint` fits.
+    ///
+    /// Nothing is known about where the lines fall, so a write
+    /// set that fits may still overflow one set of the cache.
+    pub const fn check(&self, footprint: Footprint) -> Fit {
+        worst(
+            compare(footprint.writes, self.write_lines),
+            compare(footprint.reads, self.read_lines),
+        )
+    }
+
+    /// Estimates whether the recorded transaction fits, counting
+    /// the written lines of each set of the cache as well.
+    pub fn check_trace(&self, trace: &Trace) -> Fit {
+        let sets = self.write_lines / self.ways.max(1);
+        worst(
+            self.check(trace.footprint()),
+            compare(trace.busiest_set(sets), self.ways),
+        )
+    }
+}
+
+impl Default for Capacity {
+    fn default() -> Capacity {
+        Capacity::L1
+    }
+}
+
+const fn compare(used: usize, limit: usize) -> Fit {
+    if used > limit {
+        Fit::Exceeds
+    } else if used > limit / 2 {
+        Fit::Tight
+    } else {
+        Fit::Fits
+    }
+}
+
+const fn worst(a: Fit, b: Fit) -> Fit {
+    match (a, b) {
+        (Fit::Exceeds, _) | (_, Fit::Exceeds) => Fit::Exceeds,
+        (Fit::Tight, _) | (_, Fit::Tight) => Fit::Tight,
+        _ => Fit::Fits,
+    }
+}
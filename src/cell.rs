@@ -0,0 +1,105 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Memory cells for transactional data.
+
+use core::cell::UnsafeCell;
+
+/// A `Copy` value that is read and written inside transactions.
+///
+/// Under RTM reads and writes are plain memory operations, the
+/// hardware tracks them. On the fallback path of `transaction`
+/// and under the `emulated` feature each write is logged first,
+/// so an abort can put the old value back. Logging needs the
+/// `std` feature.
+///
+/// A `TxCell` is `Sync` so transactions on several threads may
+/// share it. Accesses are only synchronized when they happen
+/// inside a transaction or under a fallback lock of this crate,
+/// which is why `get` and `set` are `unsafe`. Use `get_mut` when
+/// you hold the cell uniquely.
+pub struct TxCell<T: Copy> {
+    value: UnsafeCell<T>,
+}
+unsafe impl<T: Copy + Send> Send for TxCell<T> {}
+unsafe impl<T: Copy + Send> Sync for TxCell<T> {}
+
+impl<T: Copy> TxCell<T> {
+    /// Creates a new cell.
+    #[inline]
+    pub const fn new(value: T) -> TxCell<T> {
+        TxCell {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Reads the value.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may write the cell at the same time. This
+    /// holds inside a transaction or fallback of this crate when
+    /// every thread that writes the cell also goes through one
+    /// covering it, or when no other thread can reach the cell.
+    #[inline]
+    pub unsafe fn get(&self) -> T {
+        #[cfg(feature = "emulated")]
+        crate::emulated::access(self.value.get() as usize, core::mem::size_of::<T>(), false);
+
+        self.value.get().read_volatile()
+    }
+
+    /// Writes the value.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may read or write the cell at the same
+    /// time, see `get`.
+    #[inline]
+    pub unsafe fn set(&self, value: T) {
+        #[cfg(feature = "std")]
+        crate::undo::log_write(self.value.get() as *mut u8, core::mem::size_of::<T>());
+        #[cfg(feature = "emulated")]
+        crate::emulated::access(self.value.get() as usize, core::mem::size_of::<T>(), true);
+
+        self.value.get().write_volatile(value)
+    }
+
+    /// Returns a raw pointer to the value.
+    #[inline]
+    pub(crate) fn as_ptr(&self) -> *mut T {
+        self.value.get()
+    }
+
+    /// Returns a mutable reference to the value, no transaction
+    /// is needed as we hold a unique borrow.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Consumes the cell returning the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Copy + Default> Default for TxCell<T> {
+    fn default() -> TxCell<T> {
+        TxCell::new(T::default())
+    }
+}
@@ -0,0 +1,110 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Runtime detection of RTM.
+//!
+//! Compiling with `-Ctarget-feature=+rtm` says nothing about
+//! the machine the binary ends up on. Microcode updates have
+//! disabled TSX on many parts that advertised it at launch, so
+//! the check is made once at runtime and every entry point
+//! consults it.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+#[cfg(not(feature = "std"))]
+use core::arch::x86_64::{__cpuid, __cpuid_count};
+
+#[cfg(feature = "std")]
+use std::arch::x86_64::{__cpuid, __cpuid_count};
+
+const UNKNOWN: u8 = 0;
+const ABSENT: u8 = 1;
+const PRESENT: u8 = 2;
+
+/// CPUID.(EAX=7,ECX=0):EBX bit 11
+const CPUID_RTM: u32 = 1 << 11;
+
+/// CPUID.(EAX=7,ECX=0):EDX bit 11, every `_xbegin` aborts.
+const CPUID_RTM_ALWAYS_ABORT: u32 = 1 << 11;
+
+/// CPUID.(EAX=1):ECX bit 13
+const CPUID_CMPXCHG16B: u32 = 1 << 13;
+
+static STATE: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+static CMPXCHG16B: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+/// Returns `true` if this processor can run RTM transactions.
+///
+/// The processor must advertise RTM (`CPUID.7.EBX[11]`) and
+/// must not report `RTM_ALWAYS_ABORT` (`CPUID.7.EDX[11]`), which
+/// is what parts with TSX disabled through `TSX_CTRL` (or the
+/// TAA/SRBDS microcode updates) report. When TSX is disabled via
+/// `TSX_CPUID_CLEAR` the RTM bit itself is cleared.
+///
+/// The result is cached after the first call. With the
+/// `emulated` feature this is always `true`.
+#[inline]
+pub fn is_supported() -> bool {
+    if cfg!(feature = "emulated") {
+        return true;
+    }
+    match STATE.load(Ordering::Relaxed) {
+        PRESENT => true,
+        ABSENT => false,
+        _ => probe(),
+    }
+}
+
+#[cold]
+fn probe() -> bool {
+    // Miri cannot run CPUID, it has no RTM either
+    let max_leaf = if cfg!(miri) { 0 } else { __cpuid(0).eax };
+    let supported = if max_leaf < 7 {
+        false
+    } else {
+        let leaf = __cpuid_count(7, 0);
+        cpuid_supports_rtm(max_leaf, leaf.ebx, leaf.edx)
+    };
+    STATE.store(if supported { PRESENT } else { ABSENT }, Ordering::Relaxed);
+    supported
+}
+
+/// The decision `is_supported` makes from the highest basic
+/// CPUID leaf and the `EBX`/`EDX` of leaf 7. Exposed for tests.
+#[doc(hidden)]
+#[inline]
+pub fn cpuid_supports_rtm(max_leaf: u32, leaf7_ebx: u32, leaf7_edx: u32) -> bool {
+    max_leaf >= 7 && leaf7_ebx & CPUID_RTM != 0 && leaf7_edx & CPUID_RTM_ALWAYS_ABORT == 0
+}
+
+/// Returns `true` if this processor has `cmpxchg16b`, which a
+/// few early x86_64 parts lack. Cached like `is_supported`.
+#[inline]
+pub(crate) fn has_cmpxchg16b() -> bool {
+    match CMPXCHG16B.load(Ordering::Relaxed) {
+        PRESENT => true,
+        ABSENT => false,
+        _ => probe_cmpxchg16b(),
+    }
+}
+
+#[cold]
+fn probe_cmpxchg16b() -> bool {
+    let present = __cpuid(1).ecx & CPUID_CMPXCHG16B != 0;
+    CMPXCHG16B.store(if present { PRESENT } else { ABSENT }, Ordering::Relaxed);
+    present
+}
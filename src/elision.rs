@@ -0,0 +1,331 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Shared plumbing for the lock elision types.
+//!
+//! A hardware transaction is started and the fallback
+//! lock word is read inside of it. Reading the word puts
+//! its cache line in the read set, so a thread taking the
+//! real lock aborts every elided critical section at once.
+
+use crate::{AbortStatus, FallbackLock, RetryPolicy};
+
+/// The call site an elided section was entered from.
+///
+/// Carries the counters of the `stats` feature and the location
+/// reported by the `tracing` feature, without either this is
+/// empty and recording compiles to nothing.
+#[derive(Copy, Clone)]
+pub(crate) struct Site {
+    #[cfg(feature = "stats")]
+    counters: Option<&'static crate::stats::Counters>,
+    #[cfg(feature = "tracing")]
+    location: &'static core::panic::Location<'static>,
+}
+
+impl Site {
+    /// The site of the first caller not marked `track_caller`.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    #[inline(always)]
+    pub(crate) fn caller() -> Site {
+        Site {
+            #[cfg(feature = "stats")]
+            counters: crate::stats::counters(core::panic::Location::caller()),
+            #[cfg(feature = "tracing")]
+            location: core::panic::Location::caller(),
+        }
+    }
+
+    /// Enters the span of hardware attempt number `attempt`, it
+    /// must be dropped outside of the transactional region.
+    #[inline(always)]
+    fn enter(self, attempt: usize) -> Entered {
+        let _ = attempt;
+        Entered {
+            #[cfg(feature = "tracing")]
+            _span: crate::trace::span(self.location, attempt),
+        }
+    }
+
+    #[inline(always)]
+    fn attempt(self) {
+        #[cfg(feature = "stats")]
+        {
+            if let Option::Some(counters) = self.counters {
+                counters.attempt();
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn commit(self) {
+        #[cfg(feature = "stats")]
+        {
+            if let Option::Some(counters) = self.counters {
+                counters.commit();
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn abort(self, attempt: usize, status: AbortStatus) {
+        #[cfg(feature = "stats")]
+        {
+            if let Option::Some(counters) = self.counters {
+                counters.abort(status);
+            }
+        }
+        #[cfg(feature = "tracing")]
+        crate::trace::abort(self.location, attempt, status);
+        let _ = (attempt, status);
+    }
+
+    /// Reports data of type `name` spanning `lines` cache lines,
+    /// more than a write set can hold.
+    #[inline(always)]
+    pub(crate) fn oversized(self, name: &'static str, lines: usize) {
+        #[cfg(feature = "tracing")]
+        crate::trace::oversized(self.location, name, lines);
+        let _ = (name, lines);
+    }
+
+    /// Records that the critical section runs under the fallback,
+    /// `status` being the last abort.
+    #[inline(always)]
+    pub(crate) fn fallback(self, status: AbortStatus) {
+        #[cfg(feature = "stats")]
+        {
+            if let Option::Some(counters) = self.counters {
+                counters.fallback();
+            }
+        }
+        #[cfg(feature = "tracing")]
+        crate::trace::fallback(self.location, status);
+        let _ = status;
+    }
+}
+
+/// The span of an attempt. An elided critical section left open
+/// by `elide` keeps it until after `commit`.
+pub(crate) struct Entered {
+    #[cfg(feature = "tracing")]
+    _span: tracing::span::EnteredSpan,
+}
+
+/// Attempts to enter an elided critical section.
+///
+/// Returns `Ok` when the caller is now executing inside a
+/// hardware transaction that observed the fallback lock as
+/// free. Returns the last abort status once `policy` gives up,
+/// or the processor has no RTM, in which case the caller must
+/// take the real lock.
+///
+/// The transaction is left open, it is ended by `commit`. The
+/// span of the attempt is handed back and must be dropped after.
+#[inline]
+pub(crate) fn elide<P, L>(site: Site, policy: P, is_free: L) -> Result<Entered, AbortStatus>
+where
+    P: RetryPolicy,
+    L: Fn() -> bool,
+{
+    if !crate::is_supported() {
+        return Err(unsupported(site, policy, is_free));
+    }
+    unsafe { elide_rtm(site, policy, is_free, || ()) }.map(|((), span)| span)
+}
+
+/// Runs `body` as an elided critical section.
+///
+/// Unlike `elide` the transaction is committed before returning,
+/// so `body` is re-executed after every abort that is retried.
+#[inline]
+pub(crate) fn elide_with<P, L, R, F>(
+    site: Site,
+    policy: P,
+    is_free: L,
+    mut body: F,
+) -> Result<R, AbortStatus>
+where
+    P: RetryPolicy,
+    L: Fn() -> bool,
+    F: FnMut() -> R,
+{
+    if !crate::is_supported() {
+        return Err(unsupported(site, policy, is_free));
+    }
+    let output = unsafe {
+        elide_rtm(site, policy, is_free, || {
+            let output = body();
+            crate::tsx::_xend();
+            output
+        })
+    };
+    output.map(|(output, _span)| {
+        site.commit();
+        output
+    })
+}
+
+/// Runs `body` as an elided critical section of `lock`, and
+/// under `lock` itself once `policy` gives up.
+#[inline]
+pub(crate) fn elide_or_lock<P, R, F>(site: Site, policy: P, lock: &FallbackLock, mut body: F) -> R
+where
+    P: RetryPolicy,
+    F: FnMut() -> R,
+{
+    let status = match elide_with(site, policy, || !lock.is_locked(), &mut body) {
+        Ok(output) => return output,
+        Err(status) => status,
+    };
+    site.fallback(status);
+    let _guard = lock.lock();
+    body()
+}
+
+/// Without RTM no attempt can start, the status has no flags set
+/// and is not retried. Injected faults are still reported first,
+/// so schedules drive the same retries and fallbacks on any host.
+#[cold]
+fn unsupported<P, L>(site: Site, mut policy: P, is_free: L) -> AbortStatus
+where
+    P: RetryPolicy,
+    L: Fn() -> bool,
+{
+    #[cfg(feature = "fault-injection")]
+    {
+        let mut attempt = 0usize;
+        while let Option::Some(status) = crate::inject::next() {
+            attempt += 1;
+            {
+                let _span = site.enter(attempt);
+                site.attempt();
+                site.abort(attempt, status);
+                if !policy.retry(attempt, status) {
+                    return status;
+                }
+            }
+            if status.lock_busy() {
+                while !is_free() {
+                    core::hint::spin_loop();
+                }
+            }
+        }
+    }
+    let _ = (site, &mut policy, is_free);
+    AbortStatus::from_raw(0)
+}
+
+#[target_feature(enable = "rtm")]
+#[inline]
+unsafe fn elide_rtm<P, L, R, F>(
+    site: Site,
+    mut policy: P,
+    is_free: L,
+    mut body: F,
+) -> Result<(R, Entered), AbortStatus>
+where
+    P: RetryPolicy,
+    L: Fn() -> bool,
+    F: FnMut() -> R,
+{
+    let mut attempt = 0usize;
+    loop {
+        let status = {
+            let span = site.enter(attempt + 1);
+            site.attempt();
+            let status = match xbegin() {
+                crate::tsx::_XBEGIN_STARTED => {
+                    let output = catch(|| {
+                        if !is_free() {
+                            crate::tsx::_xabort::<{ crate::LOCK_BUSY as u32 }>();
+                        }
+                        body()
+                    });
+                    match output {
+                        Ok(output) => return Ok((output, span)),
+                        Err(status) => status,
+                    }
+                }
+                status => status,
+            };
+            let status = AbortStatus::from_raw(status);
+            attempt += 1;
+            site.abort(attempt, status);
+            if !policy.retry(attempt, status) {
+                return Err(status);
+            }
+            status
+        };
+        if status.lock_busy() {
+            // wait for the holder before trying again,
+            // otherwise the attempt aborts the same way
+            while !is_free() {
+                core::hint::spin_loop();
+            }
+        }
+    }
+}
+
+/// `_xbegin`, unless an injected fault fails the attempt first.
+#[target_feature(enable = "rtm")]
+#[inline]
+unsafe fn xbegin() -> u32 {
+    #[cfg(feature = "fault-injection")]
+    {
+        if let Option::Some(status) = crate::inject::next() {
+            return status.raw();
+        }
+    }
+    crate::tsx::_xbegin()
+}
+
+/// Ends the elided critical section started by `elide`.
+///
+/// Only valid after `elide` returned `Ok`.
+#[inline(always)]
+pub(crate) fn commit(site: Site) {
+    unsafe { commit_rtm() }
+    site.commit();
+}
+
+#[target_feature(enable = "rtm")]
+#[inline]
+unsafe fn commit_rtm() {
+    crate::tsx::_xend();
+}
+
+/// On hardware an abort resumes at `_xbegin`, so there is
+/// nothing to catch.
+#[cfg(not(feature = "emulated"))]
+#[inline(always)]
+fn catch<R, F>(body: F) -> Result<R, u32>
+where
+    F: FnOnce() -> R,
+{
+    Ok(body())
+}
+
+/// The emulator unwinds out of an abort, this is where the
+/// transaction resumes.
+#[cfg(feature = "emulated")]
+#[inline(always)]
+fn catch<R, F>(body: F) -> Result<R, u32>
+where
+    F: FnOnce() -> R,
+{
+    crate::emulated::catch(body)
+}
@@ -0,0 +1,278 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Software emulation of RTM.
+//!
+//! Enabled by the `emulated` feature, this replaces the
+//! intrinsics in `tsx` so every transaction in the crate can
+//! be exercised deterministically on machines without RTM.
+//!
+//! The emulator cannot rewind the processor the way hardware
+//! does. Instead:
+//!
+//! * Transactions are serialized by one process wide lock,
+//!   which every fallback lock in this crate also takes, so
+//!   transactions and fallback paths stay atomic.
+//! * `_xabort` restores everything written through `TxCell`
+//!   and `TxVec` (plain writes are **not** rolled back), using
+//!   the same undo log as the fallback path, and unwinds to the
+//!   start of the closure based APIs, which then either run the
+//!   closure again or report the abort. An abort within a guard
+//!   of `ElidedMutex` or `ElidedRwLock` has nowhere to resume
+//!   and propagates as a panic.
+//! * Aborts can be injected with `inject`, the next `_xbegin`
+//!   on this thread fails with the given status.
+//!
+//! `record` captures the cache lines transactions touch, for
+//! `Capacity::check_trace` to judge.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::undo::Abort;
+use crate::{AbortStatus, Trace};
+
+/// Nesting depth at which hardware aborts, `MAX_RTM_NEST_COUNT`.
+const MAX_NESTING: usize = 7;
+
+/// Thread that currently owns the emulated machine, zero when free.
+static OWNER: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// How many times this thread entered the machine.
+    static HELD: Cell<usize> = const { Cell::new(0) };
+
+    /// Nesting depth of the active transaction.
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+
+    /// Undo log mark of the outermost transaction.
+    static MARK: Cell<usize> = const { Cell::new(0) };
+
+    static INJECTED: RefCell<VecDeque<u32>> = const { RefCell::new(VecDeque::new()) };
+
+    /// Lines accessed by transactions while `record` runs.
+    static TRACE: RefCell<Option<Trace>> = const { RefCell::new(None) };
+}
+
+/// Makes the next `_xbegin` on this thread fail with `status`.
+///
+/// Several statuses may be queued, each is used once.
+pub fn inject(status: AbortStatus) {
+    INJECTED.with(|queue| queue.borrow_mut().push_back(status.raw()));
+}
+
+/// Drops every queued injected abort on this thread.
+pub fn clear_injected() {
+    INJECTED.with(|queue| queue.borrow_mut().clear());
+}
+
+/// Runs `body`, returning the cache lines that the emulated
+/// transactions it starts on this thread read and write.
+///
+/// Only accesses through `TxCell` (and so `TxVec` and the other
+/// containers of this crate) are seen, fallback paths are not
+/// recorded. The lines of every transaction are merged, so record
+/// a single one to estimate whether it fits.
+///
+/// ```ignore
+/// let (_, trace) = rtm::emulated::record(|| {
+///     rtm::transaction(&mut data, update, Fallback::None)
+/// });
+/// assert_eq!(Capacity::L1.check_trace(&trace), Fit::Fits);
+/// ```
+pub fn record<R, F>(body: F) -> (R, Trace)
+where
+    F: FnOnce() -> R,
+{
+    struct Restore(Option<Trace>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            TRACE.with(|trace| *trace.borrow_mut() = self.0.take());
+        }
+    }
+
+    let restore = Restore(TRACE.with(|trace| trace.replace(Some(Trace::new()))));
+    let output = body();
+    let trace = TRACE.with(|trace| trace.borrow_mut().take());
+    drop(restore);
+    (output, trace.unwrap_or_default())
+}
+
+/// Records an access made inside a transaction.
+#[inline]
+pub(crate) fn access(addr: usize, len: usize, write: bool) {
+    if active() {
+        TRACE.with(|trace| {
+            if let Option::Some(trace) = trace.borrow_mut().as_mut() {
+                trace.touch(addr, len, write);
+            }
+        });
+    }
+}
+
+/// Emulated `_xbegin`.
+///
+/// # Safety
+///
+/// Every started transaction must be ended with `_xend` on
+/// this thread.
+pub unsafe fn _xbegin() -> u32 {
+    let depth = DEPTH.with(Cell::get);
+    if depth == 0 {
+        if let Option::Some(status) = INJECTED.with(|queue| queue.borrow_mut().pop_front()) {
+            return status;
+        }
+    }
+    if depth >= MAX_NESTING {
+        abort_with(AbortStatus::NESTED);
+    }
+    enter();
+    if depth == 0 {
+        MARK.with(|mark| mark.set(crate::undo::begin()));
+    }
+    DEPTH.with(|cell| cell.set(depth + 1));
+    crate::tsx::_XBEGIN_STARTED
+}
+
+/// Emulated `_xend`.
+///
+/// # Safety
+///
+/// Like the hardware, calling this outside of a transaction
+/// is a fault (here a panic).
+pub unsafe fn _xend() {
+    let depth = DEPTH.with(Cell::get);
+    if std::thread::panicking() {
+        // the panic would have aborted a hardware transaction
+        if depth > 0 {
+            rollback();
+        }
+        return;
+    }
+    assert!(depth > 0, "_xend outside of a transaction");
+    DEPTH.with(|cell| cell.set(depth - 1));
+    if depth == 1 {
+        crate::undo::commit();
+    }
+    exit();
+}
+
+/// Emulated `_xabort`, does nothing outside of a transaction.
+///
+/// # Safety
+///
+/// Unwinds to the start of the transaction.
+pub unsafe fn _xabort<const IMM8: u32>() {
+    abort((IMM8 & 0xFF) as u8);
+}
+
+/// Emulated `_xtest`.
+///
+/// # Safety
+///
+/// Always safe, `unsafe` to match the intrinsic.
+pub unsafe fn _xtest() -> u8 {
+    (DEPTH.with(Cell::get) > 0) as u8
+}
+
+/// Aborts with an explicit `code`.
+pub(crate) fn abort(code: u8) {
+    if DEPTH.with(Cell::get) > 0 {
+        abort_with(AbortStatus::from_code(code).raw());
+    }
+}
+
+/// Returns `true` while this thread is in an emulated transaction.
+pub(crate) fn active() -> bool {
+    DEPTH.with(Cell::get) > 0
+}
+
+/// Runs `body`, returning the status if it aborted.
+///
+/// Only the outermost transaction catches an abort, as with
+/// hardware nesting is flattened. Any other panic rolls the
+/// transaction back and keeps unwinding.
+pub(crate) fn catch<R, F>(body: F) -> Result<R, u32>
+where
+    F: FnOnce() -> R,
+{
+    let outer = DEPTH.with(Cell::get) > 1;
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(output) => Ok(output),
+        Err(payload) => {
+            if active() {
+                rollback();
+            }
+            match payload.downcast::<Abort>() {
+                Ok(abort) if !outer => Err(abort.0),
+                Ok(abort) => panic::resume_unwind(abort),
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        }
+    }
+}
+
+fn abort_with(status: u32) -> ! {
+    let status = if DEPTH.with(Cell::get) > 1 {
+        status | AbortStatus::NESTED
+    } else {
+        status
+    };
+    rollback();
+    panic::resume_unwind(Box::new(Abort(status)))
+}
+
+/// Undoes every logged write and leaves the transaction.
+fn rollback() {
+    crate::undo::rollback(MARK.with(Cell::get));
+    for _ in 0..DEPTH.with(|cell| cell.replace(0)) {
+        exit();
+    }
+}
+
+/// Identifies this thread, never zero.
+fn token() -> usize {
+    thread_local! {
+        static TOKEN: u8 = const { 0 };
+    }
+    TOKEN.with(|token| token as *const u8 as usize)
+}
+
+/// Takes the machine, re-entrant for the owning thread.
+pub(crate) fn enter() {
+    let held = HELD.with(Cell::get);
+    if held == 0 {
+        let token = token();
+        while OWNER
+            .compare_exchange_weak(0, token, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            std::thread::yield_now();
+        }
+    }
+    HELD.with(|cell| cell.set(held + 1));
+}
+
+/// Releases one `enter`.
+pub(crate) fn exit() {
+    let held = HELD.with(Cell::get) - 1;
+    HELD.with(|cell| cell.set(held));
+    if held == 0 {
+        OWNER.store(0, Ordering::Release);
+    }
+}
@@ -0,0 +1,222 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Epoch based memory reclamation.
+//!
+//! A minimal version of the scheme described by Fraser in
+//! "Practical lock-freedom" (2004), used to free the descriptors
+//! of `mcas` which other threads may still be reading.
+//!
+//! A thread pins the current epoch while it may hold pointers
+//! to shared objects. Objects are retired once unlinked, and
+//! only freed two epochs later, by which time every thread that
+//! could have seen them has unpinned. The epoch only advances
+//! when every pinned thread has observed the current one.
+
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Set in the state of a participant while it is pinned, the
+/// pinned epoch is kept in the remaining bits.
+const PINNED: usize = 1;
+
+/// Retired objects a thread collects before trying to free them.
+const COLLECT: usize = 64;
+
+static EPOCH: AtomicUsize = AtomicUsize::new(0);
+
+/// Participants are never freed, a thread that exits leaves its
+/// record to be claimed by the next new thread.
+static PARTICIPANTS: AtomicPtr<Participant> = AtomicPtr::new(ptr::null_mut());
+
+/// Garbage left behind by exited threads.
+static ORPHANS: Mutex<Vec<Garbage>> = Mutex::new(Vec::new());
+
+struct Participant {
+    state: AtomicUsize,
+    in_use: AtomicBool,
+    next: *mut Participant,
+}
+
+/// An object waiting to be freed.
+struct Garbage {
+    epoch: usize,
+    ptr: *mut u8,
+    free: unsafe fn(*mut u8),
+}
+unsafe impl Send for Garbage {}
+
+struct Local {
+    participant: &'static Participant,
+    depth: Cell<usize>,
+    garbage: RefCell<Vec<Garbage>>,
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        let garbage = std::mem::take(&mut *self.garbage.borrow_mut());
+        lock_orphans().extend(garbage);
+        self.participant.state.store(0, Ordering::Release);
+        self.participant.in_use.store(false, Ordering::Release);
+    }
+}
+
+thread_local! {
+    static LOCAL: Local = Local {
+        participant: claim(),
+        depth: Cell::new(0),
+        garbage: RefCell::new(Vec::new()),
+    };
+}
+
+/// Keeps the calling thread pinned, objects it reads cannot be
+/// freed until every guard is dropped.
+pub(crate) struct Guard {
+    _not_send: PhantomData<*const ()>,
+}
+
+/// Pins the current epoch, re-entrant.
+pub(crate) fn pin() -> Guard {
+    LOCAL.with(|local| {
+        let depth = local.depth.get();
+        if depth == 0 {
+            let epoch = EPOCH.load(Ordering::Relaxed);
+            local
+                .participant
+                .state
+                .store(epoch << 1 | PINNED, Ordering::Relaxed);
+            fence(Ordering::SeqCst);
+        }
+        local.depth.set(depth + 1);
+    });
+    Guard {
+        _not_send: PhantomData,
+    }
+}
+
+impl Guard {
+    /// Frees the `Box` behind `ptr` once no pinned thread can
+    /// still be reading it.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` came from `Box::into_raw` and is no longer reachable
+    /// by threads that pin after this call.
+    pub(crate) unsafe fn retire<T>(&self, ptr: *mut T) {
+        unsafe fn free<T>(ptr: *mut u8) {
+            drop(Box::from_raw(ptr as *mut T));
+        }
+        LOCAL.with(|local| {
+            let mut garbage = local.garbage.borrow_mut();
+            garbage.push(Garbage {
+                epoch: EPOCH.load(Ordering::Relaxed),
+                ptr: ptr as *mut u8,
+                free: free::<T>,
+            });
+            if garbage.len() >= COLLECT {
+                collect(&mut garbage);
+            }
+        });
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        LOCAL.with(|local| {
+            let depth = local.depth.get() - 1;
+            local.depth.set(depth);
+            if depth == 0 {
+                local.participant.state.store(0, Ordering::Release);
+            }
+        });
+    }
+}
+
+/// Takes the record of an exited thread, or adds a new one.
+fn claim() -> &'static Participant {
+    let mut current = PARTICIPANTS.load(Ordering::Acquire);
+    while let Some(participant) = unsafe { current.as_ref() } {
+        if !participant.in_use.load(Ordering::Relaxed)
+            && participant
+                .in_use
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        {
+            return participant;
+        }
+        current = participant.next;
+    }
+    let participant = Box::leak(Box::new(Participant {
+        state: AtomicUsize::new(0),
+        in_use: AtomicBool::new(true),
+        next: ptr::null_mut(),
+    }));
+    let mut head = PARTICIPANTS.load(Ordering::Relaxed);
+    loop {
+        participant.next = head;
+        match PARTICIPANTS.compare_exchange_weak(
+            head,
+            participant,
+            Ordering::Release,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => return participant,
+            Err(current) => head = current,
+        }
+    }
+}
+
+/// Advances the epoch if every pinned thread is in it.
+fn advance() -> usize {
+    let epoch = EPOCH.load(Ordering::Relaxed);
+    fence(Ordering::SeqCst);
+    let mut current = PARTICIPANTS.load(Ordering::Acquire);
+    while let Some(participant) = unsafe { current.as_ref() } {
+        let state = participant.state.load(Ordering::Relaxed);
+        if state & PINNED != 0 && state >> 1 != epoch {
+            return epoch;
+        }
+        current = participant.next;
+    }
+    fence(Ordering::Acquire);
+    match EPOCH.compare_exchange(epoch, epoch + 1, Ordering::Release, Ordering::Relaxed) {
+        Ok(_) => epoch + 1,
+        Err(current) => current,
+    }
+}
+
+/// Frees what no pinned thread can still be reading.
+fn collect(garbage: &mut Vec<Garbage>) {
+    let epoch = advance();
+    if let Ok(mut orphans) = ORPHANS.try_lock() {
+        garbage.append(&mut orphans);
+    }
+    garbage.retain(|item| {
+        if item.epoch + 2 <= epoch {
+            unsafe { (item.free)(item.ptr) };
+            false
+        } else {
+            true
+        }
+    });
+}
+
+fn lock_orphans() -> std::sync::MutexGuard<'static, Vec<Garbage>> {
+    ORPHANS.lock().unwrap_or_else(|poison| poison.into_inner())
+}
@@ -0,0 +1,215 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Software fallback for transactions.
+//!
+//! When the hardware cannot (or will not) commit a transaction
+//! the closure is run while holding a lock instead. Hardware
+//! transactions read that same lock word, so they abort the
+//! moment a thread falls back and the two paths stay atomic
+//! with respect to one another.
+
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{AbortStatus, CacheAligned};
+
+/// Number of locks in the striped lock table.
+const STRIPES: usize = 64;
+
+/// Spin lock that hardware transactions subscribe to.
+///
+/// Any number of transactions may share a lock, they only ever
+/// read it. A thread that falls back takes it and thereby
+/// aborts every transaction that is subscribed.
+pub struct FallbackLock {
+    locked: AtomicBool,
+}
+
+impl FallbackLock {
+    /// Creates a new unlocked lock.
+    #[inline]
+    pub const fn new() -> FallbackLock {
+        FallbackLock {
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns `true` if a thread is currently on the fallback path.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// Takes the lock, waiting for the current holder.
+    ///
+    /// While the guard lives every transaction subscribed to
+    /// this lock aborts, so this may be used to run code that
+    /// is atomic with respect to those transactions.
+    #[inline]
+    pub fn lock(&self) -> FallbackLockGuard<'_> {
+        self.acquire();
+        FallbackLockGuard::new(self)
+    }
+
+    /// Takes the lock if it is free.
+    #[inline]
+    pub fn try_lock(&self) -> Option<FallbackLockGuard<'_>> {
+        if self.try_acquire() {
+            Some(FallbackLockGuard::new(self))
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn acquire(&self) {
+        loop {
+            if self.try_acquire() {
+                return;
+            }
+            while self.is_locked() {
+                core::hint::spin_loop();
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn try_acquire(&self) -> bool {
+        #[cfg(feature = "emulated")]
+        crate::emulated::enter();
+
+        let acquired = self
+            .locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok();
+
+        #[cfg(feature = "emulated")]
+        {
+            if !acquired {
+                crate::emulated::exit();
+            }
+        }
+        acquired
+    }
+
+    #[inline]
+    pub(crate) fn release(&self) {
+        self.locked.store(false, Ordering::Release);
+
+        #[cfg(feature = "emulated")]
+        crate::emulated::exit();
+    }
+}
+
+impl Default for FallbackLock {
+    fn default() -> FallbackLock {
+        FallbackLock::new()
+    }
+}
+
+/// Releases a `FallbackLock` when dropped.
+pub struct FallbackLockGuard<'a> {
+    lock: &'a FallbackLock,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a> FallbackLockGuard<'a> {
+    #[inline(always)]
+    fn new(lock: &'a FallbackLock) -> FallbackLockGuard<'a> {
+        FallbackLockGuard {
+            lock,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<'a> Drop for FallbackLockGuard<'a> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.release();
+    }
+}
+
+static GLOBAL: FallbackLock = FallbackLock::new();
+
+#[allow(clippy::declare_interior_mutable_const)]
+const STRIPE: CacheAligned<FallbackLock> = CacheAligned::new(FallbackLock::new());
+/// Each stripe is on its own cache line, otherwise taking one
+/// stripe would abort transactions subscribed to another.
+static TABLE: [CacheAligned<FallbackLock>; STRIPES] = [STRIPE; STRIPES];
+
+/// Returns the striped lock guarding `addr`.
+#[inline]
+pub(crate) fn stripe(addr: usize) -> &'static FallbackLock {
+    let line = addr >> 6;
+    &TABLE[(line ^ (line >> 6) ^ (line >> 12)) % STRIPES]
+}
+
+/// How a transaction executes when the hardware path fails.
+#[derive(Copy, Clone)]
+pub enum Fallback<'a> {
+    /// No fallback, the abort status is returned to the caller.
+    None,
+
+    /// Every fallback in the process takes one global lock.
+    Global,
+
+    /// The caller supplies the lock. Only transactions using the
+    /// same lock are atomic with respect to one another.
+    Lock(&'a FallbackLock),
+
+    /// The lock is picked from a table of striped locks by the
+    /// address of the data, so transactions over different data
+    /// rarely serialize each other.
+    ///
+    /// This is the address `data` points to. When threads share
+    /// data by passing a reference to it, every thread's `data`
+    /// is a different place, use `Fallback::stripe_of` instead.
+    Striped,
+}
+
+impl<'a> Fallback<'a> {
+    /// The striped lock `Fallback::Striped` picks for `data`.
+    ///
+    /// For transactions over a reference to shared data, this
+    /// picks the lock by the address of the data itself.
+    #[inline]
+    pub fn stripe_of<T: ?Sized>(data: &T) -> Fallback<'static> {
+        Fallback::Lock(stripe(data as *const T as *const () as usize))
+    }
+
+    /// Returns the lock protecting the data at `addr`.
+    #[inline]
+    pub(crate) fn lock_for(&self, addr: usize) -> Option<&'a FallbackLock> {
+        match *self {
+            Fallback::None => None,
+            Fallback::Global => Some(&GLOBAL),
+            Fallback::Lock(lock) => Some(lock),
+            Fallback::Striped => Some(stripe(addr)),
+        }
+    }
+}
+
+/// Reports which path committed a transaction.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Commit {
+    /// The hardware transaction committed.
+    Hardware,
+
+    /// The closure ran under the fallback lock. This holds the
+    /// status of the last hardware attempt.
+    Fallback(AbortStatus),
+}
@@ -0,0 +1,152 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Fault injection for transactions.
+//!
+//! Enabled by the `fault-injection` feature. A `Schedule`
+//! installed on a thread decides, before each hardware attempt
+//! that thread makes, whether the attempt should fail instead.
+//! A failed attempt never starts a transaction, it reports the
+//! status the hardware would have, so retries, fallbacks and
+//! elided locks all take the same path they would on a real
+//! abort.
+//!
+//! ```ignore
+//! let _guard = rtm::inject::install(
+//!     Schedule::new()
+//!         .nth(3, AbortCode::Capacity)
+//!         .random(0.2, 42, AbortCode::Conflict),
+//! );
+//! ```
+
+use std::cell::RefCell;
+
+use crate::rand::XorShift;
+use crate::AbortStatus;
+
+enum Rule {
+    Nth(u64, AbortStatus),
+    Random(f64, XorShift, AbortStatus),
+}
+
+/// Which hardware attempts on a thread fail, and how.
+///
+/// Attempts are counted from one, starting when the schedule
+/// is installed. The first rule that matches an attempt wins.
+#[derive(Default)]
+pub struct Schedule {
+    rules: Vec<Rule>,
+    attempt: u64,
+}
+
+impl Schedule {
+    /// A schedule that never injects an abort.
+    pub fn new() -> Schedule {
+        Schedule::default()
+    }
+
+    /// Fails the `attempt`th attempt with `status`.
+    ///
+    /// An `AbortCode` is turned into the status with exactly that
+    /// flag (or explicit code) set.
+    pub fn nth<A: Into<AbortStatus>>(mut self, attempt: u64, status: A) -> Schedule {
+        self.rules.push(Rule::Nth(attempt, status.into()));
+        self
+    }
+
+    /// Fails each attempt with `probability` (from `0.0` to `1.0`).
+    ///
+    /// The same `seed` always fails the same attempts.
+    pub fn random<A: Into<AbortStatus>>(
+        mut self,
+        probability: f64,
+        seed: u64,
+        status: A,
+    ) -> Schedule {
+        self.rules.push(Rule::Random(
+            probability,
+            XorShift::new(seed),
+            status.into(),
+        ));
+        self
+    }
+
+    /// Number of hardware attempts made under this schedule.
+    pub fn attempts(&self) -> u64 {
+        self.attempt
+    }
+
+    fn next(&mut self) -> Option<AbortStatus> {
+        self.attempt += 1;
+        let attempt = self.attempt;
+        let mut hit = Option::None;
+        for rule in self.rules.iter_mut() {
+            // every random rule advances so a seed replays the
+            // same decisions no matter which rule matched first
+            let status = match rule {
+                Rule::Nth(nth, status) if *nth == attempt => Option::Some(*status),
+                Rule::Nth(_, _) => Option::None,
+                Rule::Random(probability, rng, status) => {
+                    if rng.chance(*probability) {
+                        Option::Some(*status)
+                    } else {
+                        Option::None
+                    }
+                }
+            };
+            hit = hit.or(status);
+        }
+        hit
+    }
+}
+
+thread_local! {
+    static SCHEDULE: RefCell<Option<Schedule>> = const { RefCell::new(None) };
+}
+
+/// Installs `schedule` on this thread, replacing the current one
+/// until the returned guard is dropped.
+pub fn install(schedule: Schedule) -> ScheduleGuard {
+    let previous = SCHEDULE.with(|cell| cell.replace(Option::Some(schedule)));
+    ScheduleGuard {
+        previous: Option::Some(previous),
+    }
+}
+
+/// Restores the previous schedule when dropped.
+pub struct ScheduleGuard {
+    previous: Option<Option<Schedule>>,
+}
+
+impl ScheduleGuard {
+    /// Number of hardware attempts made under the installed schedule.
+    pub fn attempts(&self) -> u64 {
+        SCHEDULE.with(|cell| cell.borrow().as_ref().map_or(0, Schedule::attempts))
+    }
+}
+
+impl Drop for ScheduleGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take().unwrap_or_default();
+        SCHEDULE.with(|cell| cell.replace(previous));
+    }
+}
+
+/// Consulted before every hardware attempt.
+#[inline]
+pub(crate) fn next() -> Option<AbortStatus> {
+    SCHEDULE.with(|cell| cell.borrow_mut().as_mut().and_then(Schedule::next))
+}
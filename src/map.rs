@@ -0,0 +1,338 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A hash map whose operations run as transactions.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+use core::mem::MaybeUninit;
+use std::collections::hash_map::RandomState;
+
+use crate::elision::Site;
+use crate::{
+    AbortStatus, CacheAligned, FallbackLock, FallbackLockGuard, Fixed, TxCell, DEFAULT_RETRIES,
+};
+
+/// Entries per bucket, three word sized entries and their tags
+/// fill one cache line.
+const SLOTS: usize = 3;
+
+/// A key lives in its home bucket or one of the buckets after it.
+const PROBE: usize = 4;
+
+/// The most fallback locks a map has.
+const STRIPES: usize = 64;
+
+const EMPTY: u8 = 0;
+const TOMBSTONE: u8 = 1;
+
+/// Tag bit of an occupied slot, the low bits hold the top of
+/// the hash of its key.
+const OCCUPIED: u8 = 0x80;
+
+/// Explicit abort code of a batch that found no room for one of
+/// its entries, the whole batch is rolled back.
+const NO_ROOM: u8 = 0xFE;
+
+struct Bucket<K: Copy, V: Copy> {
+    tags: [TxCell<u8>; SLOTS],
+    entries: [TxCell<MaybeUninit<(K, V)>>; SLOTS],
+}
+
+impl<K: Copy, V: Copy> Bucket<K, V> {
+    fn new() -> Bucket<K, V> {
+        Bucket {
+            tags: core::array::from_fn(|_| TxCell::new(EMPTY)),
+            entries: core::array::from_fn(|_| TxCell::new(MaybeUninit::uninit())),
+        }
+    }
+}
+
+/// Where a key may live, computed before the transaction starts.
+#[derive(Copy, Clone)]
+struct Probe {
+    home: usize,
+    tag: u8,
+}
+
+/// A concurrent hash map of `Copy` keys and values.
+///
+/// Every operation runs as a hardware transaction over an open
+/// addressing table of cache line aligned buckets, so operations
+/// on different buckets never contend. A key is stored in its
+/// home bucket or one of the next few, when they are all full
+/// inserting it fails. The table never grows, growing would call
+/// the allocator and abort the transaction.
+///
+/// On abort the operation takes the fallback locks of the
+/// buckets it may touch, in a fixed order. Transactions subscribe
+/// to those same locks.
+///
+/// `insert_many` and `transfer` update several keys atomically.
+/// When one of their entries finds no free slot they abort with
+/// the explicit code `0xFE`, which rolls the batch back.
+///
+/// Keys are hashed before the transaction starts, only `Eq` of
+/// the keys runs inside it and must not make system calls.
+pub struct TxHashMap<K: Copy, V: Copy, S = RandomState> {
+    buckets: Box<[CacheAligned<Bucket<K, V>>]>,
+    locks: Box<[CacheAligned<FallbackLock>]>,
+    hasher: S,
+}
+
+impl<K: Copy + Hash + Eq, V: Copy> TxHashMap<K, V> {
+    /// Creates a map with room for about `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> TxHashMap<K, V> {
+        TxHashMap::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K: Copy + Hash + Eq, V: Copy, S: BuildHasher> TxHashMap<K, V, S> {
+    /// Creates a map with room for about `capacity` entries which
+    /// hashes keys with `hasher`.
+    ///
+    /// Twice the slots are allocated, so inserts rarely find every
+    /// bucket near their home full.
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> TxHashMap<K, V, S> {
+        let buckets = (capacity.div_ceil(SLOTS) * 2)
+            .next_power_of_two()
+            .max(PROBE);
+        TxHashMap {
+            buckets: (0..buckets)
+                .map(|_| CacheAligned::new(Bucket::new()))
+                .collect(),
+            locks: (0..buckets.min(STRIPES))
+                .map(|_| CacheAligned::new(FallbackLock::new()))
+                .collect(),
+            hasher,
+        }
+    }
+
+    /// The number of slots in the table.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.buckets.len() * SLOTS
+    }
+
+    /// Returns a copy of the value of `key`.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn get(&self, key: &K) -> Option<V> {
+        let probe = self.probe(key);
+        self.run(Site::caller(), &[probe], || unsafe {
+            self.find_value(probe, key)
+        })
+        .unwrap_or(None)
+    }
+
+    /// Returns `true` if the map holds `key`.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn contains_key(&self, key: &K) -> bool {
+        let probe = self.probe(key);
+        self.run(Site::caller(), &[probe], || unsafe {
+            self.find(probe, key).is_some()
+        })
+        .unwrap_or(false)
+    }
+
+    /// Inserts `value` under `key`, returning the value it
+    /// replaced.
+    ///
+    /// The entry is handed back if there is no free slot near the
+    /// home bucket of `key`.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn insert(&self, key: K, value: V) -> Result<Option<V>, (K, V)> {
+        let probe = self.probe(&key);
+        self.run(Site::caller(), &[probe], || unsafe {
+            self.put(probe, key, value)
+        })
+        .ok()
+        .and_then(|inserted| inserted)
+        .ok_or((key, value))
+    }
+
+    /// Removes `key`, returning its value.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn remove(&self, key: &K) -> Option<V> {
+        let probe = self.probe(key);
+        self.run(Site::caller(), &[probe], || unsafe {
+            self.take(probe, key)
+        })
+        .unwrap_or(None)
+    }
+
+    /// Inserts every entry as one transaction, a later entry
+    /// replaces an earlier one with the same key.
+    ///
+    /// Returns `false`, leaving the map unchanged, if one of the
+    /// entries found no free slot.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn insert_many(&self, entries: &[(K, V)]) -> bool {
+        let probes: Vec<Probe> = entries.iter().map(|(key, _)| self.probe(key)).collect();
+        self.run(Site::caller(), &probes, || unsafe {
+            for (&probe, &(key, value)) in probes.iter().zip(entries) {
+                if self.put(probe, key, value).is_none() {
+                    crate::abort_with::<NO_ROOM>();
+                }
+            }
+        })
+        .is_ok()
+    }
+
+    /// Moves the value of `from` to `to` as one transaction,
+    /// replacing any value `to` held.
+    ///
+    /// Returns `false`, leaving the map unchanged, if `from` is
+    /// missing or there is no free slot for `to`.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn transfer(&self, from: &K, to: K) -> bool {
+        let probes = [self.probe(from), self.probe(&to)];
+        self.run(Site::caller(), &probes, || unsafe {
+            let value = match self.take(probes[0], from) {
+                Option::Some(value) => value,
+                Option::None => return false,
+            };
+            if self.put(probes[1], to, value).is_none() {
+                crate::abort_with::<NO_ROOM>();
+            }
+            true
+        })
+        .unwrap_or(false)
+    }
+
+    #[inline]
+    fn probe(&self, key: &K) -> Probe {
+        let hash = self.hasher.hash_one(key);
+        Probe {
+            home: hash as usize & (self.buckets.len() - 1),
+            tag: OCCUPIED | (hash >> 57) as u8,
+        }
+    }
+}
+
+impl<K: Copy + Eq, V: Copy, S> TxHashMap<K, V, S> {
+    /// Runs `body` as a transaction over the buckets of `probes`,
+    /// or under their fallback locks.
+    ///
+    /// Fails only when `body` aborts with `NO_ROOM`, its writes
+    /// are rolled back.
+    fn run<R, F>(&self, site: Site, probes: &[Probe], mut body: F) -> Result<R, AbortStatus>
+    where
+        F: FnMut() -> R,
+    {
+        let is_free = || {
+            probes
+                .iter()
+                .all(|probe| (0..PROBE).all(|i| !self.lock(probe.home + i).is_locked()))
+        };
+        let status =
+            match crate::elision::elide_with(site, Fixed(DEFAULT_RETRIES), is_free, &mut body) {
+                Ok(output) => return Ok(output),
+                Err(status) if status.explicit_code() == Some(NO_ROOM) => return Err(status),
+                Err(status) => status,
+            };
+        site.fallback(status);
+        let _guards = self.lock_all(probes);
+        let mut output = None;
+        crate::undo::run(|| output = Some(body()))?;
+        Ok(output.expect("body ran"))
+    }
+
+    /// Takes the fallback locks of every bucket `probes` may
+    /// touch, in ascending order so two fallbacks cannot deadlock.
+    fn lock_all(&self, probes: &[Probe]) -> Vec<FallbackLockGuard<'_>> {
+        let mask = self.locks.len() - 1;
+        let mut stripes: Vec<usize> = probes
+            .iter()
+            .flat_map(|probe| (0..PROBE).map(move |i| (probe.home + i) & mask))
+            .collect();
+        stripes.sort_unstable();
+        stripes.dedup();
+        stripes
+            .into_iter()
+            .map(|stripe| self.locks[stripe].lock())
+            .collect()
+    }
+
+    #[inline]
+    fn lock(&self, bucket: usize) -> &FallbackLock {
+        &self.locks[bucket & (self.locks.len() - 1)]
+    }
+
+    #[inline]
+    fn bucket(&self, probe: Probe, i: usize) -> &Bucket<K, V> {
+        &self.buckets[(probe.home + i) & (self.buckets.len() - 1)]
+    }
+
+    /// Finds the bucket and slot holding `key`.
+    ///
+    /// Inserts take the first slot that is not occupied and slots
+    /// never become empty again, so the search ends at the first
+    /// empty slot.
+    ///
+    /// This and the helpers below touch the buckets of `probe`,
+    /// they must run inside `run` over `probe`.
+    unsafe fn find(&self, probe: Probe, key: &K) -> Option<(usize, usize)> {
+        for i in 0..PROBE {
+            let bucket = self.bucket(probe, i);
+            for slot in 0..SLOTS {
+                match bucket.tags[slot].get() {
+                    EMPTY => return None,
+                    tag if tag == probe.tag
+                        && bucket.entries[slot].get().assume_init().0 == *key =>
+                    {
+                        return Some((i, slot));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        None
+    }
+
+    unsafe fn find_value(&self, probe: Probe, key: &K) -> Option<V> {
+        let (i, slot) = self.find(probe, key)?;
+        Some(self.bucket(probe, i).entries[slot].get().assume_init().1)
+    }
+
+    /// Inserts or replaces, `None` if there is no free slot.
+    unsafe fn put(&self, probe: Probe, key: K, value: V) -> Option<Option<V>> {
+        if let Option::Some((i, slot)) = self.find(probe, &key) {
+            let entry = &self.bucket(probe, i).entries[slot];
+            let previous = entry.get().assume_init().1;
+            entry.set(MaybeUninit::new((key, value)));
+            return Some(Some(previous));
+        }
+        for i in 0..PROBE {
+            let bucket = self.bucket(probe, i);
+            for slot in 0..SLOTS {
+                if bucket.tags[slot].get() & OCCUPIED == 0 {
+                    bucket.entries[slot].set(MaybeUninit::new((key, value)));
+                    bucket.tags[slot].set(probe.tag);
+                    return Some(None);
+                }
+            }
+        }
+        None
+    }
+
+    unsafe fn take(&self, probe: Probe, key: &K) -> Option<V> {
+        let (i, slot) = self.find(probe, key)?;
+        let bucket = self.bucket(probe, i);
+        bucket.tags[slot].set(TOMBSTONE);
+        Some(bucket.entries[slot].get().assume_init().1)
+    }
+}
@@ -0,0 +1,313 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Multi-word compare-and-swap.
+//!
+//! `mcas` updates several words at once with a single hardware
+//! transaction. When RTM is unavailable, or the transaction keeps
+//! aborting, it falls back to the lock-free k-CAS of Harris,
+//! Fraser and Pratt ("A Practical Multi-Word Compare-and-Swap
+//! Operation", 2002).
+//!
+//! The software path installs descriptors in the words being
+//! updated, tagged in their two low bits, which other threads
+//! complete before using the word. Those bits are reserved:
+//! every expected and new value must have them clear (aligned
+//! pointers, or integers shifted left by two), and words updated
+//! with `mcas` must be read with `mcas_read` rather than `load`.
+//!
+//! A thread may finish an operation another one started, so
+//! a word must stay allocated for as long as any thread may
+//! still be running `mcas` on it.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::elision::Site;
+use crate::epoch::{self, Guard};
+use crate::Fixed;
+
+/// Tag of a word holding an `Rdcss` descriptor.
+const RDCSS: usize = 1;
+
+/// Tag of a word holding a `Descriptor`.
+const MCAS: usize = 2;
+
+/// The bits reserved for tags.
+const TAGS: usize = RDCSS | MCAS;
+
+/// Status of an operation that is still installing itself.
+const UNDECIDED: usize = 0;
+
+const SUCCEEDED: usize = 1;
+
+/// Status of a failed operation, the index of the word that did
+/// not match is kept in the bits above the tags.
+const FAILED: usize = 2;
+
+/// Why `mcas` did not update the words.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum McasError {
+    /// The word at `index` did not hold its expected value.
+    Mismatch { index: usize },
+
+    /// A value at `index` uses the two low bits `mcas` reserves.
+    Reserved { index: usize },
+
+    /// The word at `index` appears earlier in the list too.
+    Duplicate { index: usize },
+}
+
+/// One word of an operation, `index` is its position in the
+/// list passed to `mcas`.
+struct Entry {
+    word: *const AtomicUsize,
+    expected: usize,
+    new: usize,
+    index: usize,
+}
+
+/// A k-CAS operation, its entries sorted by address so that
+/// operations install themselves in the same order.
+struct Descriptor {
+    status: AtomicUsize,
+    entries: Vec<Entry>,
+}
+
+/// A restricted double-compare single-swap: installs `descriptor`
+/// in `word` if `word` holds `expected` and `status` is still
+/// `UNDECIDED`.
+struct Rdcss {
+    status: *const AtomicUsize,
+    word: *const AtomicUsize,
+    expected: usize,
+    descriptor: usize,
+}
+
+/// Atomically replaces every word that holds its expected value
+/// with its new one, or changes nothing.
+///
+/// A single hardware transaction is attempted first, retrying up
+/// to `DEFAULT_RETRIES` times, then the lock-free software path
+/// is taken. Either way the update is linearizable with respect
+/// to other `mcas` and `mcas_read` calls.
+#[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+pub fn mcas(words: &[(&AtomicUsize, usize, usize)]) -> Result<(), McasError> {
+    let site = Site::caller();
+    let mut entries = Vec::with_capacity(words.len());
+    for (index, &(word, expected, new)) in words.iter().enumerate() {
+        if (expected | new) & TAGS != 0 {
+            return Err(McasError::Reserved { index });
+        }
+        entries.push(Entry {
+            word,
+            expected,
+            new,
+            index,
+        });
+    }
+    entries.sort_unstable_by_key(|entry| entry.word as usize);
+    for pair in entries.windows(2) {
+        if pair[0].word == pair[1].word {
+            return Err(McasError::Duplicate {
+                index: pair[0].index.max(pair[1].index),
+            });
+        }
+    }
+
+    let fixed = Fixed(crate::DEFAULT_RETRIES);
+    match crate::elision::elide_with(site, fixed, || true, || hardware(&entries)) {
+        Ok(outcome) => return outcome,
+        Err(status) => site.fallback(status),
+    }
+
+    let guard = epoch::pin();
+    let descriptor = Box::into_raw(Box::new(Descriptor {
+        status: AtomicUsize::new(UNDECIDED),
+        entries,
+    }));
+    let status = help(&guard, descriptor as usize | MCAS);
+    unsafe { guard.retire(descriptor) };
+    match status {
+        SUCCEEDED => Ok(()),
+        status => Err(McasError::Mismatch { index: status >> 2 }),
+    }
+}
+
+/// Reads a word that may be updated by `mcas`.
+pub fn mcas_read(word: &AtomicUsize) -> usize {
+    let guard = epoch::pin();
+    loop {
+        let value = rdcss_read(&guard, word);
+        if value & TAGS != MCAS {
+            return value;
+        }
+        help(&guard, value);
+    }
+}
+
+/// The body of the hardware transaction. A word holding a
+/// descriptor belongs to a software operation in flight, which
+/// is left to the software path to complete.
+#[inline]
+fn hardware(entries: &[Entry]) -> Result<(), McasError> {
+    for entry in entries {
+        let value = unsafe { (*entry.word).load(Ordering::Relaxed) };
+        if value & TAGS != 0 {
+            crate::abort_with::<{ crate::LOCK_BUSY }>();
+        }
+        if value != entry.expected {
+            return Err(McasError::Mismatch { index: entry.index });
+        }
+    }
+    for entry in entries {
+        unsafe { (*entry.word).store(entry.new, Ordering::Relaxed) };
+    }
+    Ok(())
+}
+
+/// Runs the operation `tagged` points at to completion, returning
+/// its status.
+fn help(guard: &Guard, tagged: usize) -> usize {
+    let descriptor = unsafe { &*((tagged & !TAGS) as *const Descriptor) };
+    if descriptor.status.load(Ordering::Acquire) == UNDECIDED {
+        let mut outcome = SUCCEEDED;
+        'entries: for entry in descriptor.entries.iter() {
+            loop {
+                let value = rdcss(
+                    guard,
+                    Rdcss {
+                        status: &descriptor.status,
+                        word: entry.word,
+                        expected: entry.expected,
+                        descriptor: tagged,
+                    },
+                );
+                if value & TAGS == MCAS {
+                    if value == tagged {
+                        break;
+                    }
+                    // another operation owns the word, finish it
+                    help(guard, value);
+                    continue;
+                }
+                if value != entry.expected {
+                    outcome = FAILED | entry.index << 2;
+                    break 'entries;
+                }
+                break;
+            }
+        }
+        let _ = descriptor.status.compare_exchange(
+            UNDECIDED,
+            outcome,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        );
+    }
+    // whoever installs the descriptor after the decision (see
+    // `complete`) passes through here too, so no word is left
+    // pointing at it once every helper has unpinned
+    let status = descriptor.status.load(Ordering::Acquire);
+    for entry in descriptor.entries.iter() {
+        let value = if status == SUCCEEDED {
+            entry.new
+        } else {
+            entry.expected
+        };
+        cas(entry.word, tagged, value);
+    }
+    status
+}
+
+/// Installs `rdcss` in its word, returning the value it found.
+fn rdcss(guard: &Guard, rdcss: Rdcss) -> usize {
+    let rdcss = Box::into_raw(Box::new(rdcss));
+    let (word, expected) = unsafe { ((*rdcss).word, (*rdcss).expected) };
+    let tagged = rdcss as usize | RDCSS;
+    loop {
+        let value = cas(word, expected, tagged);
+        if value & TAGS == RDCSS {
+            complete(value);
+            continue;
+        }
+        if value == expected {
+            complete(tagged);
+            unsafe { guard.retire(rdcss) };
+        } else {
+            // never published
+            drop(unsafe { Box::from_raw(rdcss) });
+        }
+        return value;
+    }
+}
+
+/// Reads `word`, completing any `Rdcss` found in it.
+fn rdcss_read(_: &Guard, word: &AtomicUsize) -> usize {
+    loop {
+        let value = load(word);
+        if value & TAGS != RDCSS {
+            return value;
+        }
+        complete(value);
+    }
+}
+
+/// Replaces the `Rdcss` `tagged` points at with the operation it
+/// installs, or with the expected value again if that operation
+/// has already been decided.
+fn complete(tagged: usize) {
+    let rdcss = unsafe { &*((tagged & !TAGS) as *const Rdcss) };
+    let status = unsafe { (*rdcss.status).load(Ordering::Acquire) };
+    let value = if status == UNDECIDED {
+        rdcss.descriptor
+    } else {
+        rdcss.expected
+    };
+    cas(rdcss.word, tagged, value);
+}
+
+/// Compare-and-swap returning the value found in `word`.
+#[inline]
+fn cas(word: *const AtomicUsize, current: usize, new: usize) -> usize {
+    #[cfg(feature = "emulated")]
+    crate::emulated::enter();
+
+    let word = unsafe { &*word };
+    let found = match word.compare_exchange(current, new, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(found) => found,
+        Err(found) => found,
+    };
+
+    #[cfg(feature = "emulated")]
+    crate::emulated::exit();
+
+    found
+}
+
+/// Loads `word`. Under the emulator this waits for emulated
+/// transactions, which write their words one at a time.
+#[inline]
+fn load(word: &AtomicUsize) -> usize {
+    #[cfg(feature = "emulated")]
+    crate::emulated::enter();
+
+    let value = word.load(Ordering::Acquire);
+
+    #[cfg(feature = "emulated")]
+    crate::emulated::exit();
+
+    value
+}
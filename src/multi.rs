@@ -0,0 +1,156 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Transactions over several independently owned objects.
+
+use alloc::vec::Vec;
+
+use crate::elision::Site;
+use crate::{AbortStatus, Commit, Fixed, RetryPolicy, TxCell, TxVec};
+
+/// Shared data that can take part in `transaction_many`.
+///
+/// The fallback lock of an object is picked from its address,
+/// the same way `Fallback::Striped` picks it, so `transaction`
+/// with `Fallback::Striped` over the object and `transaction_many`
+/// including it are atomic with respect to one another.
+///
+/// Writes must go through `TxCell`, `TxVec` or similar so the
+/// fallback path can roll them back.
+pub trait TxObject {
+    /// The address the fallback lock is picked by.
+    #[inline]
+    fn lock_addr(&self) -> usize {
+        self as *const Self as *const () as usize
+    }
+}
+
+impl<T: Copy> TxObject for TxCell<T> {}
+impl<T: Copy> TxObject for TxVec<T> {}
+
+/// A tuple or array of references to `TxObject`s.
+pub trait TxHandles: Copy {
+    /// Calls `f` with the lock address of every object.
+    fn each_lock_addr<F: FnMut(usize)>(&self, f: F);
+}
+
+impl<T: TxObject + ?Sized, const N: usize> TxHandles for [&T; N] {
+    #[inline]
+    fn each_lock_addr<F: FnMut(usize)>(&self, mut f: F) {
+        for object in self {
+            f(object.lock_addr());
+        }
+    }
+}
+
+macro_rules! tuple_handles {
+    ($($name: ident),*) => {
+        impl<$($name: TxObject + ?Sized),*> TxHandles for ($(&$name,)*) {
+            #[inline]
+            #[allow(non_snake_case)]
+            fn each_lock_addr<Visit: FnMut(usize)>(&self, mut f: Visit) {
+                let ($($name,)*) = *self;
+                $(f($name.lock_addr());)*
+            }
+        }
+    };
+}
+tuple_handles!(A);
+tuple_handles!(A, B);
+tuple_handles!(A, B, C);
+tuple_handles!(A, B, C, D);
+tuple_handles!(A, B, C, D, E);
+tuple_handles!(A, B, C, D, E, F);
+tuple_handles!(A, B, C, D, E, F, G);
+tuple_handles!(A, B, C, D, E, F, G, H);
+
+/// Runs `lambda` over every object in `handles` as one
+/// transaction.
+///
+/// `transaction` needs a `&mut` to its data, so two structures
+/// owned apart (say two accounts in their own `Arc`s) cannot be
+/// updated together without aliasing tricks. Here each object is
+/// handed over by shared reference and written through its cells.
+///
+/// ```ignore
+/// rtm::transaction_many((&*from, &*to), |(from, to)| unsafe {
+///     from.set(from.get() - 10);
+///     to.set(to.get() + 10);
+/// })?;
+/// ```
+///
+/// The fallback takes the stripe lock of every object, as
+/// `Fallback::Striped` picks it, once each and ordered by the
+/// address of the lock. So fallbacks over overlapping objects
+/// cannot deadlock and fallbacks over disjoint ones rarely wait
+/// for each other. An
+/// `abort` on the fallback path rolls back and returns its
+/// status, as with `transaction`.
+#[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+pub fn transaction_many<H, F>(handles: H, lambda: F) -> Result<Commit, AbortStatus>
+where
+    H: TxHandles,
+    F: FnMut(H),
+{
+    execute_many(Site::caller(), handles, lambda, Fixed(0))
+}
+
+/// Unlike `transaction_many` this function can perform retries,
+/// see `transaction_retry`.
+#[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+pub fn transaction_many_retry<H, F, P>(
+    handles: H,
+    lambda: F,
+    policy: P,
+) -> Result<Commit, AbortStatus>
+where
+    H: TxHandles,
+    F: FnMut(H),
+    P: RetryPolicy,
+{
+    execute_many(Site::caller(), handles, lambda, policy)
+}
+
+#[inline(always)]
+fn execute_many<H, F, P>(
+    site: Site,
+    handles: H,
+    mut lambda: F,
+    policy: P,
+) -> Result<Commit, AbortStatus>
+where
+    H: TxHandles,
+    F: FnMut(H),
+    P: RetryPolicy,
+{
+    let is_free = || {
+        let mut free = true;
+        handles.each_lock_addr(|addr| free &= !crate::fallback::stripe(addr).is_locked());
+        free
+    };
+    let status = match crate::elision::elide_with(site, policy, is_free, || lambda(handles)) {
+        Ok(()) => return Ok(Commit::Hardware),
+        Err(status) => status,
+    };
+    site.fallback(status);
+    let mut locks = Vec::new();
+    handles.each_lock_addr(|addr| locks.push(crate::fallback::stripe(addr)));
+    locks.sort_unstable_by_key(|lock| *lock as *const _ as usize);
+    locks.dedup_by_key(|lock| *lock as *const _ as usize);
+    let _guards: Vec<_> = locks.into_iter().map(|lock| lock.lock()).collect();
+    crate::undo::run(|| lambda(handles))?;
+    Ok(Commit::Fallback(status))
+}
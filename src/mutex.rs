@@ -0,0 +1,210 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A mutex that elides its lock with RTM.
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+use crate::elision::{Entered, Site};
+use crate::{FallbackLock, Fixed, RetryPolicy};
+
+/// Number of hardware retries `ElidedMutex::new` makes
+/// before taking the fallback lock.
+pub const DEFAULT_RETRIES: usize = 3;
+
+/// Mutual exclusion primitive that first attempts to run the
+/// critical section as a hardware transaction.
+///
+/// The fallback lock is read inside of the transaction, so
+/// any number of threads may run an elided critical section
+/// concurrently as long as they do not touch the same cache
+/// lines. Once the retry policy gives up the real (spin) lock
+/// is taken.
+///
+/// On processors without RTM every `lock` goes straight to
+/// the fallback lock, so the same code runs everywhere.
+///
+/// Anything done while holding the guard of an elided lock
+/// happens inside a transaction. System calls, I/O, and
+/// similar will abort it and the section will be restarted
+/// from `lock`.
+pub struct ElidedMutex<T: ?Sized, P = Fixed> {
+    lock: FallbackLock,
+    policy: P,
+    data: UnsafeCell<T>,
+}
+unsafe impl<T: ?Sized + Send, P: Send> Send for ElidedMutex<T, P> {}
+unsafe impl<T: ?Sized + Send, P: Sync> Sync for ElidedMutex<T, P> {}
+
+impl<T> ElidedMutex<T> {
+    /// Creates a new mutex which makes `DEFAULT_RETRIES`
+    /// hardware retries.
+    #[inline]
+    pub const fn new(data: T) -> ElidedMutex<T> {
+        ElidedMutex::with_retries(data, DEFAULT_RETRIES)
+    }
+
+    /// Creates a new mutex which makes `retries` hardware
+    /// retries before taking the fallback lock.
+    #[inline]
+    pub const fn with_retries(data: T, retries: usize) -> ElidedMutex<T> {
+        ElidedMutex::with_policy(data, Fixed(retries))
+    }
+}
+
+impl<T, P> ElidedMutex<T, P> {
+    /// Creates a new mutex which consults a copy of `policy`
+    /// on every acquisition before taking the fallback lock.
+    #[inline]
+    pub const fn with_policy(data: T, policy: P) -> ElidedMutex<T, P> {
+        ElidedMutex {
+            lock: FallbackLock::new(),
+            policy,
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Consumes the mutex returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized, P: RetryPolicy + Clone> ElidedMutex<T, P> {
+    /// Acquires the mutex, either by starting a transaction
+    /// or by taking the fallback lock.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn lock(&self) -> ElidedMutexGuard<'_, T> {
+        let site = Site::caller();
+        let status =
+            match crate::elision::elide(site, self.policy.clone(), || !self.lock.is_locked()) {
+                Ok(span) => return ElidedMutexGuard::new(self, Option::Some(span), site),
+                Err(status) => status,
+            };
+        site.fallback(status);
+        self.lock.acquire();
+        ElidedMutexGuard::new(self, Option::None, site)
+    }
+}
+
+impl<T: ?Sized, P> ElidedMutex<T, P> {
+    /// Attempts to acquire the mutex without waiting.
+    ///
+    /// A single hardware attempt is made, then a single attempt
+    /// at the fallback lock.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn try_lock(&self) -> Option<ElidedMutexGuard<'_, T>> {
+        let site = Site::caller();
+        let status = match crate::elision::elide(site, Fixed(0), || !self.lock.is_locked()) {
+            Ok(span) => return Some(ElidedMutexGuard::new(self, Option::Some(span), site)),
+            Err(status) => status,
+        };
+        if self.lock.try_acquire() {
+            site.fallback(status);
+            Some(ElidedMutexGuard::new(self, Option::None, site))
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the fallback lock is currently held.
+    ///
+    /// Elided critical sections never hold it.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.lock.is_locked()
+    }
+
+    /// Returns a mutable reference to the underlying data,
+    /// no locking is needed as we hold a unique borrow.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+impl<T: Default> Default for ElidedMutex<T> {
+    fn default() -> ElidedMutex<T> {
+        ElidedMutex::new(T::default())
+    }
+}
+
+/// Scoped access to the data of an `ElidedMutex`.
+///
+/// Dropping the guard either commits the transaction or
+/// releases the fallback lock. The guard cannot leave the
+/// thread, a transaction must end where it started.
+pub struct ElidedMutexGuard<'a, T: ?Sized + 'a> {
+    lock: &'a FallbackLock,
+    data: &'a UnsafeCell<T>,
+    span: Option<Entered>,
+    site: Site,
+    _not_send: PhantomData<*const ()>,
+}
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for ElidedMutexGuard<'a, T> {}
+
+impl<'a, T: ?Sized + 'a> ElidedMutexGuard<'a, T> {
+    #[inline(always)]
+    fn new<P>(
+        mutex: &'a ElidedMutex<T, P>,
+        span: Option<Entered>,
+        site: Site,
+    ) -> ElidedMutexGuard<'a, T> {
+        ElidedMutexGuard {
+            lock: &mutex.lock,
+            data: &mutex.data,
+            span,
+            site,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Returns `true` if this critical section is running
+    /// inside a hardware transaction.
+    #[inline]
+    pub fn is_elided(&self) -> bool {
+        self.span.is_some()
+    }
+}
+
+impl<'a, T: ?Sized + 'a> Deref for ElidedMutexGuard<'a, T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized + 'a> DerefMut for ElidedMutexGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized + 'a> Drop for ElidedMutexGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.span.is_some() {
+            crate::elision::commit(self.site);
+        } else {
+            self.lock.release();
+        }
+    }
+}
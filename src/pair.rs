@@ -0,0 +1,281 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Double-width atomics.
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+
+use crate::elision::Site;
+use crate::{AbortStatus, FallbackLock, Fixed};
+
+/// A value that fits in one half of an `AtomicPair`.
+///
+/// Implemented for the integer types of up to 64 bits, `bool`
+/// and raw pointers.
+pub trait PairWord: Copy {
+    /// Converts the value to the bits stored in the pair.
+    fn into_bits(self) -> u64;
+
+    /// Converts bits produced by `into_bits` back.
+    fn from_bits(bits: u64) -> Self;
+}
+
+macro_rules! pair_word {
+    ($($t: ty),*) => {
+        $(
+            impl PairWord for $t {
+                #[inline(always)]
+                fn into_bits(self) -> u64 {
+                    self as u64
+                }
+
+                #[inline(always)]
+                fn from_bits(bits: u64) -> $t {
+                    bits as $t
+                }
+            }
+        )*
+    };
+}
+
+pair_word!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl PairWord for bool {
+    #[inline(always)]
+    fn into_bits(self) -> u64 {
+        self as u64
+    }
+
+    #[inline(always)]
+    fn from_bits(bits: u64) -> bool {
+        bits != 0
+    }
+}
+
+impl<T> PairWord for *const T {
+    #[inline(always)]
+    fn into_bits(self) -> u64 {
+        self as usize as u64
+    }
+
+    #[inline(always)]
+    fn from_bits(bits: u64) -> *const T {
+        bits as usize as *const T
+    }
+}
+
+impl<T> PairWord for *mut T {
+    #[inline(always)]
+    fn into_bits(self) -> u64 {
+        self as usize as u64
+    }
+
+    #[inline(always)]
+    fn from_bits(bits: u64) -> *mut T {
+        bits as usize as *mut T
+    }
+}
+
+/// Two values updated together atomically, such as a pointer
+/// and an ABA counter.
+///
+/// Every operation is first attempted as a single hardware
+/// transaction, then with `cmpxchg16b`. On the rare x86_64 parts
+/// without `cmpxchg16b` a striped fallback lock is taken instead,
+/// which hardware transactions subscribe to. The pair is aligned
+/// to 16 bytes as `cmpxchg16b` requires.
+#[repr(C, align(16))]
+pub struct AtomicPair<A: PairWord, B: PairWord> {
+    bits: UnsafeCell<u128>,
+    _marker: PhantomData<(A, B)>,
+}
+unsafe impl<A: PairWord, B: PairWord> Send for AtomicPair<A, B> {}
+unsafe impl<A: PairWord, B: PairWord> Sync for AtomicPair<A, B> {}
+
+impl<A: PairWord, B: PairWord> AtomicPair<A, B> {
+    /// Creates a new pair.
+    #[inline]
+    pub fn new(a: A, b: B) -> AtomicPair<A, B> {
+        AtomicPair {
+            bits: UnsafeCell::new(pack(a, b)),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Loads both values.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn load(&self) -> (A, B) {
+        let ptr = self.bits.get();
+        let bits = match self.elide(Site::caller(), || unsafe { ptr.read_volatile() }) {
+            Ok(bits) => bits,
+            Err(_) => self.cas(0, 0),
+        };
+        unpack(bits)
+    }
+
+    /// Stores both values.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn store(&self, a: A, b: B) {
+        self.swap(a, b);
+    }
+
+    /// Stores both values, returning the previous ones.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn swap(&self, a: A, b: B) -> (A, B) {
+        let new = pack(a, b);
+        let ptr = self.bits.get();
+        let swapped = self.elide(Site::caller(), || unsafe {
+            let previous = ptr.read_volatile();
+            ptr.write_volatile(new);
+            previous
+        });
+        let mut current = match swapped {
+            Ok(previous) => return unpack(previous),
+            Err(_) => self.cas(0, 0),
+        };
+        loop {
+            match self.cas(current, new) {
+                found if found == current => return unpack(found),
+                found => current = found,
+            }
+        }
+    }
+
+    /// Stores `new` if the pair holds `current`, comparing the
+    /// bits of both values.
+    ///
+    /// Returns the previous values, wrapped in `Err` when they
+    /// did not match.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn compare_exchange(&self, current: (A, B), new: (A, B)) -> Result<(A, B), (A, B)> {
+        let current = pack(current.0, current.1);
+        let new = pack(new.0, new.1);
+        let ptr = self.bits.get();
+        let exchanged = self.elide(Site::caller(), || unsafe {
+            let previous = ptr.read_volatile();
+            if previous == current {
+                ptr.write_volatile(new);
+            }
+            previous
+        });
+        let previous = match exchanged {
+            Ok(previous) => previous,
+            Err(_) => self.cas(current, new),
+        };
+        if previous == current {
+            Ok(unpack(previous))
+        } else {
+            Err(unpack(previous))
+        }
+    }
+
+    /// Consumes the pair returning both values.
+    #[inline]
+    pub fn into_inner(self) -> (A, B) {
+        unpack(self.bits.into_inner())
+    }
+
+    /// Makes a single hardware attempt at `body`.
+    #[inline]
+    fn elide<R, F>(&self, site: Site, body: F) -> Result<R, AbortStatus>
+    where
+        F: FnMut() -> R,
+    {
+        match self.lock() {
+            Option::Some(lock) => {
+                crate::elision::elide_with(site, Fixed(0), || !lock.is_locked(), body)
+            }
+            Option::None => crate::elision::elide_with(site, Fixed(0), || true, body),
+        }
+    }
+
+    /// Compare-and-swap of the raw bits, returning the bits
+    /// found in the pair.
+    fn cas(&self, current: u128, new: u128) -> u128 {
+        let ptr = self.bits.get();
+        match self.lock() {
+            Option::Some(lock) => {
+                let _guard = lock.lock();
+                unsafe {
+                    let previous = ptr.read_volatile();
+                    if previous == current {
+                        ptr.write_volatile(new);
+                    }
+                    previous
+                }
+            }
+            Option::None => {
+                #[cfg(feature = "emulated")]
+                crate::emulated::enter();
+
+                let previous = unsafe { cmpxchg16b(ptr, current, new) };
+
+                #[cfg(feature = "emulated")]
+                crate::emulated::exit();
+
+                previous
+            }
+        }
+    }
+
+    /// The striped lock guarding the pair, only used when the
+    /// processor has no `cmpxchg16b`.
+    #[inline]
+    fn lock(&self) -> Option<&'static FallbackLock> {
+        if crate::detect::has_cmpxchg16b() {
+            None
+        } else {
+            Some(crate::fallback::stripe(self.bits.get() as usize))
+        }
+    }
+}
+
+impl<A: PairWord + Default, B: PairWord + Default> Default for AtomicPair<A, B> {
+    fn default() -> AtomicPair<A, B> {
+        AtomicPair::new(A::default(), B::default())
+    }
+}
+
+#[inline(always)]
+fn pack<A: PairWord, B: PairWord>(a: A, b: B) -> u128 {
+    a.into_bits() as u128 | (b.into_bits() as u128) << 64
+}
+
+#[inline(always)]
+fn unpack<A: PairWord, B: PairWord>(bits: u128) -> (A, B) {
+    (A::from_bits(bits as u64), B::from_bits((bits >> 64) as u64))
+}
+
+/// `lock cmpxchg16b`, written out as the intrinsic falls back to a
+/// libcall unless the whole crate is built with the feature.
+/// `rbx` belongs to LLVM so the low half of `new` is swapped in.
+#[inline]
+unsafe fn cmpxchg16b(ptr: *mut u128, current: u128, new: u128) -> u128 {
+    let (lo, hi): (u64, u64);
+    core::arch::asm!(
+        "xchg {new_lo}, rbx",
+        "lock cmpxchg16b xmmword ptr [{ptr}]",
+        "mov rbx, {new_lo}",
+        ptr = in(reg) ptr,
+        new_lo = inout(reg) new as u64 => _,
+        in("rcx") (new >> 64) as u64,
+        inout("rax") current as u64 => lo,
+        inout("rdx") (current >> 64) as u64 => hi,
+        options(nostack),
+    );
+    lo as u128 | (hi as u128) << 64
+}
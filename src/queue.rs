@@ -0,0 +1,155 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A bounded multi-producer multi-consumer queue.
+
+use alloc::boxed::Box;
+use core::mem::MaybeUninit;
+
+use crate::elision::Site;
+use crate::{CacheAligned, CachePadded, FallbackLock, Fixed, TxCell, DEFAULT_RETRIES};
+
+/// A bounded FIFO queue of `Copy` values shared by any number of
+/// producers and consumers.
+///
+/// Each operation updates the head or tail and the slot in one
+/// hardware transaction, so there are no sequence numbers, ABA
+/// tags or helping as in lock-free queues. Once the transaction
+/// gives up the operation runs under a spin lock, which the
+/// transactions subscribe to.
+///
+/// The capacity is fixed when the queue is created.
+pub struct TxQueue<T: Copy> {
+    head: CachePadded<TxCell<usize>>,
+    tail: CachePadded<TxCell<usize>>,
+    lock: CacheAligned<FallbackLock>,
+    slots: Box<[TxCell<MaybeUninit<T>>]>,
+}
+
+impl<T: Copy> TxQueue<T> {
+    /// Creates an empty queue holding at most `capacity` values.
+    pub fn with_capacity(capacity: usize) -> TxQueue<T> {
+        TxQueue {
+            head: CachePadded::new(TxCell::new(0)),
+            tail: CachePadded::new(TxCell::new(0)),
+            lock: CacheAligned::new(FallbackLock::new()),
+            slots: (0..capacity)
+                .map(|_| TxCell::new(MaybeUninit::uninit()))
+                .collect(),
+        }
+    }
+
+    /// The most values the queue can hold.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// The number of values in the queue.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn len(&self) -> usize {
+        self.run(Site::caller(), || unsafe { self.count() })
+    }
+
+    /// Returns `true` if the queue holds no values.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends a value at the tail, handing it back if the queue
+    /// is full.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn push(&self, value: T) -> Result<(), T> {
+        self.run(Site::caller(), || unsafe {
+            if self.count() == self.capacity() {
+                return Err(value);
+            }
+            self.put(value);
+            Ok(())
+        })
+    }
+
+    /// Removes the value at the head.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn pop(&self) -> Option<T> {
+        self.run(Site::caller(), || unsafe {
+            if self.count() == 0 {
+                return None;
+            }
+            Some(self.take())
+        })
+    }
+
+    /// Appends every value as one transaction.
+    ///
+    /// Returns `false`, leaving the queue unchanged, if there is
+    /// not room for all of them.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn push_many(&self, values: &[T]) -> bool {
+        self.run(Site::caller(), || unsafe {
+            if self.capacity() - self.count() < values.len() {
+                return false;
+            }
+            for &value in values {
+                self.put(value);
+            }
+            true
+        })
+    }
+
+    /// Removes up to `out.len()` values from the head as one
+    /// transaction, returning how many were written to `out`.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn pop_many(&self, out: &mut [T]) -> usize {
+        self.run(Site::caller(), || unsafe {
+            let n = self.count().min(out.len());
+            for slot in &mut out[..n] {
+                *slot = self.take();
+            }
+            n
+        })
+    }
+
+    #[inline(always)]
+    fn run<R, F>(&self, site: Site, body: F) -> R
+    where
+        F: FnMut() -> R,
+    {
+        crate::elision::elide_or_lock(site, Fixed(DEFAULT_RETRIES), &self.lock, body)
+    }
+
+    /// The head, tail and slots may only be touched inside `run`,
+    /// so this and the helpers below must run there.
+    #[inline]
+    unsafe fn count(&self) -> usize {
+        self.tail.get() - self.head.get()
+    }
+
+    #[inline]
+    unsafe fn put(&self, value: T) {
+        let tail = self.tail.get();
+        self.slots[tail % self.capacity()].set(MaybeUninit::new(value));
+        self.tail.set(tail + 1);
+    }
+
+    #[inline]
+    unsafe fn take(&self) -> T {
+        let head = self.head.get();
+        self.head.set(head + 1);
+        self.slots[head % self.capacity()].get().assume_init()
+    }
+}
@@ -0,0 +1,226 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Retry policies for hardware transactions.
+//!
+//! After every failed hardware attempt the policy is handed the
+//! full abort status and decides whether another attempt is
+//! worth making. It may wait before answering, which is where
+//! backoff happens. When it declines, the caller moves on to the
+//! fallback path (or reports the abort).
+
+#[cfg(not(feature = "std"))]
+use core::arch::x86_64::_mm_pause;
+
+#[cfg(feature = "std")]
+use std::arch::x86_64::_mm_pause;
+
+use crate::rand::XorShift;
+use crate::{AbortStatus, FallbackLock};
+
+/// Decides whether a failed hardware attempt is retried.
+pub trait RetryPolicy {
+    /// Called after each failed attempt with the number of
+    /// attempts made so far (starting at one) and the status of
+    /// the last one. Returns `true` to make another attempt.
+    fn retry(&mut self, attempt: usize, status: AbortStatus) -> bool;
+}
+
+impl<P: RetryPolicy + ?Sized> RetryPolicy for &mut P {
+    #[inline]
+    fn retry(&mut self, attempt: usize, status: AbortStatus) -> bool {
+        (**self).retry(attempt, status)
+    }
+}
+
+/// A bare count behaves as `Fixed`.
+impl RetryPolicy for usize {
+    #[inline]
+    fn retry(&mut self, attempt: usize, status: AbortStatus) -> bool {
+        Fixed(*self).retry(attempt, status)
+    }
+}
+
+/// `None` makes no retries, `Some(n)` behaves as `Fixed(n)`.
+impl RetryPolicy for Option<usize> {
+    #[inline]
+    fn retry(&mut self, attempt: usize, status: AbortStatus) -> bool {
+        Fixed(self.unwrap_or(0)).retry(attempt, status)
+    }
+}
+
+/// Retries up to `n` times, only when the hardware set the retry
+/// flag (or the fallback lock was observed held). Retries are
+/// made immediately.
+///
+/// `n` counts retries, so at most `n + 1` attempts are made. The
+/// `retries` of `transaction_retry` in 2.0 stopped after `n`
+/// attempts in total, one retry short of its documentation.
+#[derive(Copy, Clone, Debug)]
+pub struct Fixed(pub usize);
+
+impl RetryPolicy for Fixed {
+    #[inline]
+    fn retry(&mut self, attempt: usize, status: AbortStatus) -> bool {
+        attempt <= self.0 && (status.retry() || status.lock_busy())
+    }
+}
+
+/// Retries up to `retries` times on anything but a capacity or
+/// explicit abort, which would only happen again.
+///
+/// Conflicts are frequently transient even when the hardware
+/// does not set the retry flag for them.
+#[derive(Copy, Clone, Debug)]
+pub struct RetryConflicts {
+    /// Maximum number of retries.
+    pub retries: usize,
+}
+
+impl RetryPolicy for RetryConflicts {
+    #[inline]
+    fn retry(&mut self, attempt: usize, status: AbortStatus) -> bool {
+        attempt <= self.retries && transient(status)
+    }
+}
+
+/// Retries like `RetryConflicts`, but pauses between attempts for
+/// a number of `_mm_pause` instructions that doubles each time.
+#[derive(Copy, Clone, Debug)]
+pub struct Backoff {
+    /// Maximum number of retries.
+    pub retries: usize,
+
+    /// Pauses before the first retry, `0` is taken as `1` so
+    /// the pauses still grow.
+    pub min: u32,
+
+    /// Upper bound on the pauses before any retry.
+    pub max: u32,
+}
+
+impl Backoff {
+    /// The number of pauses made before retrying after `attempt`.
+    #[inline]
+    pub fn pauses(&self, attempt: usize) -> u32 {
+        exponential(self.min, self.max, attempt)
+    }
+}
+
+impl RetryPolicy for Backoff {
+    #[inline]
+    fn retry(&mut self, attempt: usize, status: AbortStatus) -> bool {
+        if attempt > self.retries || !transient(status) {
+            return false;
+        }
+        pause(self.pauses(attempt));
+        true
+    }
+}
+
+/// Retries like `Backoff`, but the pause before each retry is
+/// picked at random below the exponential bound, so threads that
+/// conflicted with each other do not retry in lock step.
+#[derive(Copy, Clone, Debug)]
+pub struct Jitter {
+    retries: usize,
+    min: u32,
+    max: u32,
+    rng: XorShift,
+}
+
+impl Jitter {
+    /// Creates the policy, the same `seed` replays the same pauses.
+    ///
+    /// A `min` of `0` is taken as `1`, as for `Backoff`.
+    pub const fn new(retries: usize, min: u32, max: u32, seed: u64) -> Jitter {
+        Jitter {
+            retries,
+            min: if min == 0 { 1 } else { min },
+            max,
+            rng: XorShift::new(seed),
+        }
+    }
+
+    /// Picks the number of pauses made before retrying after
+    /// `attempt`, at most what `Backoff` would make.
+    #[inline]
+    pub fn pauses(&mut self, attempt: usize) -> u32 {
+        let bound = exponential(self.min, self.max, attempt) as u64 + 1;
+        (self.rng.next() % bound) as u32
+    }
+}
+
+impl RetryPolicy for Jitter {
+    #[inline]
+    fn retry(&mut self, attempt: usize, status: AbortStatus) -> bool {
+        if attempt > self.retries || !transient(status) {
+            return false;
+        }
+        let count = self.pauses(attempt);
+        pause(count);
+        true
+    }
+}
+
+/// Waits until `lock` is free before every retry.
+///
+/// Meant for transactions subscribed to `lock`. Once another
+/// thread is on the fallback path, attempting again before it
+/// leaves can only abort, so rather than spending the retries
+/// this waits it out. Up to `retries` retries are made on
+/// anything but a capacity or explicit abort.
+#[derive(Copy, Clone)]
+pub struct UntilUnlocked<'a> {
+    /// Lock the transaction is subscribed to.
+    pub lock: &'a FallbackLock,
+
+    /// Maximum number of retries.
+    pub retries: usize,
+}
+
+impl<'a> RetryPolicy for UntilUnlocked<'a> {
+    #[inline]
+    fn retry(&mut self, attempt: usize, status: AbortStatus) -> bool {
+        if attempt > self.retries || !transient(status) {
+            return false;
+        }
+        while self.lock.is_locked() {
+            pause(1);
+        }
+        true
+    }
+}
+
+/// Everything but capacity and explicit aborts may succeed
+/// on another attempt.
+#[inline]
+fn transient(status: AbortStatus) -> bool {
+    status.lock_busy() || !(status.capacity() || status.explicit())
+}
+
+#[inline]
+fn exponential(min: u32, max: u32, attempt: usize) -> u32 {
+    let shift = core::cmp::min(attempt.saturating_sub(1), 31) as u32;
+    core::cmp::min(core::cmp::max(min, 1).saturating_mul(1 << shift), max)
+}
+
+#[inline]
+fn pause(count: u32) {
+    for _ in 0..count {
+        _mm_pause();
+    }
+}
@@ -0,0 +1,361 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A reader-writer lock that elides its lock with RTM.
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::elision::{Entered, Site};
+use crate::{Fixed, RetryPolicy};
+
+/// Set in the lock word while a writer holds the fallback lock.
+const WRITER: usize = 1;
+
+/// Set in the lock word while a writer waits for the fallback
+/// lock, new fallback readers hold off until it got it.
+const WAITING: usize = 2;
+
+/// Each reader holding the fallback lock adds this to the word.
+const READER: usize = 4;
+
+/// Reader-writer lock that first attempts to run both read
+/// and write sections as hardware transactions.
+///
+/// Elided readers only ever read the lock word, so on the
+/// fast path they never write to its cache line and do not
+/// contend with one another. Elided writers additionally
+/// require that no fallback reader is present. Once the
+/// retry policy gives up the real (spin) reader-writer lock
+/// is taken.
+///
+/// The fallback lock prefers writers: while a writer waits for
+/// it no new reader takes it. Fallback readers count themselves
+/// in the lock word, so each one taking or releasing the lock
+/// aborts the elided sections running at the time. Those are
+/// retried and mostly commit again, the cost is only paid while
+/// sections actually fall back.
+///
+/// On processors without RTM every acquisition goes straight
+/// to the fallback lock.
+pub struct ElidedRwLock<T: ?Sized, P = Fixed> {
+    state: AtomicUsize,
+    policy: P,
+    data: UnsafeCell<T>,
+}
+unsafe impl<T: ?Sized + Send, P: Send> Send for ElidedRwLock<T, P> {}
+unsafe impl<T: ?Sized + Send + Sync, P: Sync> Sync for ElidedRwLock<T, P> {}
+
+impl<T> ElidedRwLock<T> {
+    /// Creates a new lock which makes `DEFAULT_RETRIES`
+    /// hardware retries.
+    #[inline]
+    pub const fn new(data: T) -> ElidedRwLock<T> {
+        ElidedRwLock::with_retries(data, crate::DEFAULT_RETRIES)
+    }
+
+    /// Creates a new lock which makes `retries` hardware
+    /// retries before taking the fallback lock.
+    #[inline]
+    pub const fn with_retries(data: T, retries: usize) -> ElidedRwLock<T> {
+        ElidedRwLock::with_policy(data, Fixed(retries))
+    }
+}
+
+impl<T, P> ElidedRwLock<T, P> {
+    /// Creates a new lock which consults a copy of `policy`
+    /// on every acquisition before taking the fallback lock.
+    #[inline]
+    pub const fn with_policy(data: T, policy: P) -> ElidedRwLock<T, P> {
+        ElidedRwLock {
+            state: AtomicUsize::new(0),
+            policy,
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Consumes the lock returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized, P: RetryPolicy + Clone> ElidedRwLock<T, P> {
+    /// Acquires shared access, either by starting a transaction
+    /// or by taking the fallback lock for reading.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn read(&self) -> ElidedRwLockReadGuard<'_, T> {
+        let site = Site::caller();
+        let status = match crate::elision::elide(site, self.policy.clone(), || self.no_writer()) {
+            Ok(span) => return ElidedRwLockReadGuard::new(self, Option::Some(span), site),
+            Err(status) => status,
+        };
+        site.fallback(status);
+        loop {
+            if self.try_read_fallback() {
+                return ElidedRwLockReadGuard::new(self, Option::None, site);
+            }
+            while self.state.load(Ordering::Relaxed) & (WRITER | WAITING) != 0 {
+                core::hint::spin_loop();
+            }
+        }
+    }
+
+    /// Acquires exclusive access, either by starting a
+    /// transaction or by taking the fallback lock for writing.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn write(&self) -> ElidedRwLockWriteGuard<'_, T> {
+        let site = Site::caller();
+        let status = match crate::elision::elide(site, self.policy.clone(), || self.unlocked()) {
+            Ok(span) => return ElidedRwLockWriteGuard::new(self, Option::Some(span), site),
+            Err(status) => status,
+        };
+        site.fallback(status);
+        loop {
+            if self.try_write_fallback() {
+                return ElidedRwLockWriteGuard::new(self, Option::None, site);
+            }
+            // taking the lock clears the bit, a writer that lost
+            // the race sets it again here
+            self.state.fetch_or(WAITING, Ordering::Relaxed);
+            while self.state.load(Ordering::Relaxed) & !WAITING != 0 {
+                core::hint::spin_loop();
+            }
+        }
+    }
+}
+
+impl<T: ?Sized, P> ElidedRwLock<T, P> {
+    /// Attempts to acquire shared access without waiting.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn try_read(&self) -> Option<ElidedRwLockReadGuard<'_, T>> {
+        let site = Site::caller();
+        let status = match crate::elision::elide(site, Fixed(0), || self.no_writer()) {
+            Ok(span) => return Some(ElidedRwLockReadGuard::new(self, Option::Some(span), site)),
+            Err(status) => status,
+        };
+        if self.try_read_fallback() {
+            site.fallback(status);
+            Some(ElidedRwLockReadGuard::new(self, Option::None, site))
+        } else {
+            None
+        }
+    }
+
+    /// Attempts to acquire exclusive access without waiting.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn try_write(&self) -> Option<ElidedRwLockWriteGuard<'_, T>> {
+        let site = Site::caller();
+        let status = match crate::elision::elide(site, Fixed(0), || self.unlocked()) {
+            Ok(span) => return Some(ElidedRwLockWriteGuard::new(self, Option::Some(span), site)),
+            Err(status) => status,
+        };
+        if self.try_write_fallback() {
+            site.fallback(status);
+            Some(ElidedRwLockWriteGuard::new(self, Option::None, site))
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the underlying data,
+    /// no locking is needed as we hold a unique borrow.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+
+    #[inline]
+    fn no_writer(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER == 0
+    }
+
+    #[inline]
+    fn unlocked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) == 0
+    }
+
+    #[inline]
+    fn try_read_fallback(&self) -> bool {
+        #[cfg(feature = "emulated")]
+        crate::emulated::enter();
+
+        let state = self.state.load(Ordering::Relaxed);
+        let acquired = state & (WRITER | WAITING) == 0
+            && self
+                .state
+                .compare_exchange(state, state + READER, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok();
+
+        #[cfg(feature = "emulated")]
+        {
+            if !acquired {
+                crate::emulated::exit();
+            }
+        }
+        acquired
+    }
+
+    #[inline]
+    fn try_write_fallback(&self) -> bool {
+        #[cfg(feature = "emulated")]
+        crate::emulated::enter();
+
+        let state = self.state.load(Ordering::Relaxed);
+        let acquired = state & !WAITING == 0
+            && self
+                .state
+                .compare_exchange(state, WRITER, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok();
+
+        #[cfg(feature = "emulated")]
+        {
+            if !acquired {
+                crate::emulated::exit();
+            }
+        }
+        acquired
+    }
+}
+
+impl<T: Default> Default for ElidedRwLock<T> {
+    fn default() -> ElidedRwLock<T> {
+        ElidedRwLock::new(T::default())
+    }
+}
+
+/// Scoped shared access to the data of an `ElidedRwLock`.
+pub struct ElidedRwLockReadGuard<'a, T: ?Sized + 'a> {
+    state: &'a AtomicUsize,
+    data: &'a UnsafeCell<T>,
+    span: Option<Entered>,
+    site: Site,
+    _not_send: PhantomData<*const ()>,
+}
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for ElidedRwLockReadGuard<'a, T> {}
+
+impl<'a, T: ?Sized + 'a> ElidedRwLockReadGuard<'a, T> {
+    #[inline(always)]
+    fn new<P>(
+        lock: &'a ElidedRwLock<T, P>,
+        span: Option<Entered>,
+        site: Site,
+    ) -> ElidedRwLockReadGuard<'a, T> {
+        ElidedRwLockReadGuard {
+            state: &lock.state,
+            data: &lock.data,
+            span,
+            site,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Returns `true` if this critical section is running
+    /// inside a hardware transaction.
+    #[inline]
+    pub fn is_elided(&self) -> bool {
+        self.span.is_some()
+    }
+}
+
+impl<'a, T: ?Sized + 'a> Deref for ElidedRwLockReadGuard<'a, T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized + 'a> Drop for ElidedRwLockReadGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.span.is_some() {
+            crate::elision::commit(self.site);
+        } else {
+            self.state.fetch_sub(READER, Ordering::Release);
+
+            #[cfg(feature = "emulated")]
+            crate::emulated::exit();
+        }
+    }
+}
+
+/// Scoped exclusive access to the data of an `ElidedRwLock`.
+pub struct ElidedRwLockWriteGuard<'a, T: ?Sized + 'a> {
+    state: &'a AtomicUsize,
+    data: &'a UnsafeCell<T>,
+    span: Option<Entered>,
+    site: Site,
+    _not_send: PhantomData<*const ()>,
+}
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for ElidedRwLockWriteGuard<'a, T> {}
+
+impl<'a, T: ?Sized + 'a> ElidedRwLockWriteGuard<'a, T> {
+    #[inline(always)]
+    fn new<P>(
+        lock: &'a ElidedRwLock<T, P>,
+        span: Option<Entered>,
+        site: Site,
+    ) -> ElidedRwLockWriteGuard<'a, T> {
+        ElidedRwLockWriteGuard {
+            state: &lock.state,
+            data: &lock.data,
+            span,
+            site,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Returns `true` if this critical section is running
+    /// inside a hardware transaction.
+    #[inline]
+    pub fn is_elided(&self) -> bool {
+        self.span.is_some()
+    }
+}
+
+impl<'a, T: ?Sized + 'a> Deref for ElidedRwLockWriteGuard<'a, T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized + 'a> DerefMut for ElidedRwLockWriteGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized + 'a> Drop for ElidedRwLockWriteGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.span.is_some() {
+            crate::elision::commit(self.site);
+        } else {
+            // keeps `WAITING`, set by writers that came meanwhile
+            self.state.fetch_and(!WRITER, Ordering::Release);
+
+            #[cfg(feature = "emulated")]
+            crate::emulated::exit();
+        }
+    }
+}
@@ -0,0 +1,344 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! An ordered map built as a skiplist.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::mem::MaybeUninit;
+use core::ops::{Bound, RangeBounds};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::elision::Site;
+use crate::{CacheAligned, CachePadded, FallbackLock, Fixed, TxCell, DEFAULT_RETRIES};
+
+/// Levels of the list, with one in four nodes promoted per level
+/// searches stay logarithmic up to about 65k entries.
+const LEVELS: usize = 8;
+
+/// Index of the head node, as a link it marks the end of a level.
+const NIL: u32 = 0;
+
+/// Free lists, spread so inserts and removals on different
+/// threads do not all write the same line.
+const SHARDS: usize = 8;
+
+/// The most entries a range scan copies out per transaction.
+const CHUNK: usize = 32;
+
+/// Word sized keys and values, the height and the links fill
+/// one cache line.
+struct Node<K: Copy, V: Copy> {
+    entry: TxCell<MaybeUninit<(K, V)>>,
+    height: TxCell<u8>,
+    next: [TxCell<u32>; LEVELS],
+}
+
+/// A concurrent ordered map of `Copy` keys and values.
+///
+/// Lookups, inserts and removals each run as one hardware
+/// transaction, which only reads and writes the few nodes along
+/// the search path. Nodes are cache line aligned and come from a
+/// pool allocated up front, the allocator would abort the
+/// transaction, so the number of entries is bounded.
+///
+/// `range` copies entries out in chunks, each chunk being its own
+/// transaction. A chunk that aborts on capacity is retried at
+/// half the size.
+///
+/// Once a transaction gives up the operation runs under a spin
+/// lock, which the transactions subscribe to. `Ord` of the keys
+/// runs inside the transaction, it must not make system calls.
+pub struct TxSkipList<K: Copy, V: Copy> {
+    nodes: Box<[CacheAligned<Node<K, V>>]>,
+    free: [CacheAligned<TxCell<u32>>; SHARDS],
+    lock: CacheAligned<FallbackLock>,
+    /// Written by `draw` outside of any transaction, on lines of
+    /// its own so that does not abort the transactions reading
+    /// `nodes`.
+    seed: CachePadded<AtomicU64>,
+}
+
+impl<K: Copy + Ord, V: Copy> TxSkipList<K, V> {
+    /// Creates an empty map holding at most `capacity` entries.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` does not fit a `u32`.
+    pub fn with_capacity(capacity: usize) -> TxSkipList<K, V> {
+        assert!(
+            capacity < u32::MAX as usize,
+            "capacity {} too large",
+            capacity
+        );
+        let nodes: Box<[CacheAligned<Node<K, V>>]> = (0..=capacity)
+            .map(|_| {
+                CacheAligned::new(Node {
+                    entry: TxCell::new(MaybeUninit::uninit()),
+                    height: TxCell::new(0),
+                    next: core::array::from_fn(|_| TxCell::new(NIL)),
+                })
+            })
+            .collect();
+        let free: [CacheAligned<TxCell<u32>>; SHARDS] =
+            core::array::from_fn(|_| CacheAligned::new(TxCell::new(NIL)));
+        for index in 1..=capacity as u32 {
+            let shard = &free[index as usize % SHARDS];
+            // Neither is shared yet.
+            unsafe {
+                nodes[index as usize].next[0].set(shard.get());
+                shard.set(index);
+            }
+        }
+        TxSkipList {
+            nodes,
+            free,
+            lock: CacheAligned::new(FallbackLock::new()),
+            seed: CachePadded::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// The most entries the map can hold.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.nodes.len() - 1
+    }
+
+    /// Returns a copy of the value of `key`.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.run(Site::caller(), || unsafe {
+            let mut preds = [NIL; LEVELS];
+            let node = self.search(key, &mut preds);
+            self.matches(node, key).then(|| self.entry(node).1)
+        })
+    }
+
+    /// Returns `true` if the map holds `key`.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.run(Site::caller(), || unsafe {
+            let mut preds = [NIL; LEVELS];
+            let node = self.search(key, &mut preds);
+            self.matches(node, key)
+        })
+    }
+
+    /// Inserts `value` under `key`, returning the value it
+    /// replaced.
+    ///
+    /// The entry is handed back if every node is in use.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn insert(&self, key: K, value: V) -> Result<Option<V>, (K, V)> {
+        let (height, shard) = self.draw();
+        self.run(Site::caller(), || unsafe {
+            let mut preds = [NIL; LEVELS];
+            let node = self.search(&key, &mut preds);
+            if self.matches(node, &key) {
+                let previous = self.entry(node).1;
+                self.nodes[node as usize]
+                    .entry
+                    .set(MaybeUninit::new((key, value)));
+                return Ok(Some(previous));
+            }
+            let node = match self.alloc(shard) {
+                Option::Some(node) => node,
+                Option::None => return Err((key, value)),
+            };
+            let new = &self.nodes[node as usize];
+            new.entry.set(MaybeUninit::new((key, value)));
+            new.height.set(height as u8);
+            for (level, &pred) in preds.iter().enumerate().take(height) {
+                let link = &self.nodes[pred as usize].next[level];
+                new.next[level].set(link.get());
+                link.set(node);
+            }
+            Ok(None)
+        })
+    }
+
+    /// Removes `key`, returning its value.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.run(Site::caller(), || unsafe {
+            let mut preds = [NIL; LEVELS];
+            let node = self.search(key, &mut preds);
+            if !self.matches(node, key) {
+                return None;
+            }
+            let value = self.entry(node).1;
+            let old = &self.nodes[node as usize];
+            for (level, &pred) in preds.iter().enumerate().take(old.height.get() as usize) {
+                self.nodes[pred as usize].next[level].set(old.next[level].get());
+            }
+            let shard = &self.free[node as usize % SHARDS];
+            old.next[0].set(shard.get());
+            shard.set(node);
+            Some(value)
+        })
+    }
+
+    /// Copies out the entries within `range`, in order.
+    ///
+    /// The entries are read in chunks, each chunk is atomic but
+    /// the scan as a whole is not. Every key is seen at most once,
+    /// keys inserted behind the scan are missed.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Vec<(K, V)> {
+        let site = Site::caller();
+        let mut start = range.start_bound().cloned();
+        let end = range.end_bound().cloned();
+        let mut out = Vec::new();
+        let mut chunk = CHUNK;
+        loop {
+            let mut buf = [MaybeUninit::uninit(); CHUNK];
+            let mut body = || unsafe { self.collect(start, end, &mut buf[..chunk]) };
+            let is_free = || !self.lock.is_locked();
+            let (n, done) = match crate::elision::elide_with(
+                site,
+                Fixed(DEFAULT_RETRIES),
+                is_free,
+                &mut body,
+            ) {
+                Ok(collected) => collected,
+                Err(status) if status.capacity() && chunk > 1 => {
+                    chunk /= 2;
+                    continue;
+                }
+                Err(status) => {
+                    site.fallback(status);
+                    let _guard = self.lock.lock();
+                    body()
+                }
+            };
+            out.extend(buf[..n].iter().map(|entry| unsafe { entry.assume_init() }));
+            match out.last() {
+                Option::Some(&(last, _)) if !done => start = Bound::Excluded(last),
+                _ => return out,
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn run<R, F>(&self, site: Site, body: F) -> R
+    where
+        F: FnMut() -> R,
+    {
+        crate::elision::elide_or_lock(site, Fixed(DEFAULT_RETRIES), &self.lock, body)
+    }
+
+    /// Picks the height of a new node and the free list to take
+    /// it from, outside of the transaction.
+    fn draw(&self) -> (usize, usize) {
+        let mut x = self
+            .seed
+            .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed);
+        // splitmix64 finalizer
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        x ^= x >> 31;
+        let height = (x.trailing_zeros() as usize / 2 + 1).min(LEVELS);
+        (height, (x >> 61) as usize % SHARDS)
+    }
+
+    /// Takes a node from the free lists, starting at `shard`.
+    ///
+    /// The nodes and free lists may only be touched inside `run`
+    /// or a chunk of `range`, this and the helpers below must run
+    /// there.
+    unsafe fn alloc(&self, shard: usize) -> Option<u32> {
+        for i in 0..SHARDS {
+            let head = &self.free[(shard + i) % SHARDS];
+            let node = head.get();
+            if node != NIL {
+                head.set(self.nodes[node as usize].next[0].get());
+                return Some(node);
+            }
+        }
+        None
+    }
+
+    #[inline]
+    unsafe fn entry(&self, node: u32) -> (K, V) {
+        self.nodes[node as usize].entry.get().assume_init()
+    }
+
+    #[inline]
+    unsafe fn matches(&self, node: u32, key: &K) -> bool {
+        node != NIL && self.entry(node).0 == *key
+    }
+
+    /// Fills `preds` with the last node before `key` on every
+    /// level, returning the first node not before it.
+    unsafe fn search(&self, key: &K, preds: &mut [u32; LEVELS]) -> u32 {
+        self.seek(|k| k < *key, preds)
+    }
+
+    /// Walks past every node whose key satisfies `before`.
+    unsafe fn seek<F>(&self, before: F, preds: &mut [u32; LEVELS]) -> u32
+    where
+        F: Fn(K) -> bool,
+    {
+        let mut node = NIL;
+        for level in (0..LEVELS).rev() {
+            loop {
+                let next = self.nodes[node as usize].next[level].get();
+                if next == NIL || !before(self.entry(next).0) {
+                    break;
+                }
+                node = next;
+            }
+            preds[level] = node;
+        }
+        self.nodes[node as usize].next[0].get()
+    }
+
+    /// Copies the entries from `start` on into `buf`, returning
+    /// how many were copied and whether `end` or the end of the
+    /// list was reached.
+    unsafe fn collect(
+        &self,
+        start: Bound<K>,
+        end: Bound<K>,
+        buf: &mut [MaybeUninit<(K, V)>],
+    ) -> (usize, bool) {
+        let mut preds = [NIL; LEVELS];
+        let mut node = match start {
+            Bound::Included(start) => self.seek(|k| k < start, &mut preds),
+            Bound::Excluded(start) => self.seek(|k| k <= start, &mut preds),
+            Bound::Unbounded => self.nodes[0].next[0].get(),
+        };
+        let mut n = 0;
+        while node != NIL {
+            let entry = self.entry(node);
+            let within = match end {
+                Bound::Included(end) => entry.0 <= end,
+                Bound::Excluded(end) => entry.0 < end,
+                Bound::Unbounded => true,
+            };
+            if !within {
+                return (n, true);
+            }
+            if n == buf.len() {
+                return (n, false);
+            }
+            buf[n] = MaybeUninit::new(entry);
+            n += 1;
+            node = self.nodes[node as usize].next[0].get();
+        }
+        (n, true)
+    }
+}
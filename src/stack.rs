@@ -0,0 +1,120 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A bounded concurrent stack.
+
+use crate::elision::Site;
+use crate::{FallbackLock, Fixed, TxVec, DEFAULT_RETRIES};
+
+/// A bounded LIFO stack of `Copy` values shared by any number of
+/// threads.
+///
+/// Each operation updates the top and the slot in one hardware
+/// transaction, which sidesteps the ABA problem of lock-free
+/// stacks. Once the transaction gives up the operation runs under
+/// a spin lock, which the transactions subscribe to.
+///
+/// The capacity is fixed when the stack is created.
+pub struct TxStack<T: Copy> {
+    items: TxVec<T>,
+    lock: FallbackLock,
+}
+
+impl<T: Copy> TxStack<T> {
+    /// Creates an empty stack holding at most `capacity` values.
+    pub fn with_capacity(capacity: usize) -> TxStack<T> {
+        TxStack {
+            items: TxVec::with_capacity(capacity),
+            lock: FallbackLock::new(),
+        }
+    }
+
+    /// The most values the stack can hold.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.items.capacity()
+    }
+
+    /// The number of values on the stack.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn len(&self) -> usize {
+        self.run(Site::caller(), || unsafe { self.items.len() })
+    }
+
+    /// Returns `true` if the stack holds no values.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pushes a value, handing it back if the stack is full.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn push(&self, value: T) -> Result<(), T> {
+        self.run(Site::caller(), || unsafe { self.items.push(value) })
+    }
+
+    /// Pops the most recently pushed value.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn pop(&self) -> Option<T> {
+        self.run(Site::caller(), || unsafe { self.items.pop() })
+    }
+
+    /// Pushes every value as one transaction, the last value ends
+    /// up on top.
+    ///
+    /// Returns `false`, leaving the stack unchanged, if there is
+    /// not room for all of them.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn push_many(&self, values: &[T]) -> bool {
+        self.run(Site::caller(), || unsafe {
+            if self.capacity() - self.items.len() < values.len() {
+                return false;
+            }
+            for &value in values {
+                let _ = self.items.push(value);
+            }
+            true
+        })
+    }
+
+    /// Pops up to `out.len()` values as one transaction, the top
+    /// of the stack first, returning how many were written to
+    /// `out`.
+    #[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+    pub fn pop_many(&self, out: &mut [T]) -> usize {
+        self.run(Site::caller(), || unsafe {
+            let mut n = 0;
+            for slot in out.iter_mut() {
+                match self.items.pop() {
+                    Option::Some(value) => *slot = value,
+                    Option::None => break,
+                }
+                n += 1;
+            }
+            n
+        })
+    }
+
+    /// Runs `body` as a transaction or under the lock, `items`
+    /// may only be touched there.
+    #[inline(always)]
+    fn run<R, F>(&self, site: Site, body: F) -> R
+    where
+        F: FnMut() -> R,
+    {
+        crate::elision::elide_or_lock(site, Fixed(DEFAULT_RETRIES), &self.lock, body)
+    }
+}
@@ -0,0 +1,593 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Transaction statistics.
+//!
+//! Enabled with the `stats` feature. Every hardware attempt,
+//! commit, abort and fallback execution made through this crate
+//! is counted per call site, the location of the call to
+//! `transaction`, `ElidedMutex::lock` and so on.
+//!
+//! Each thread owns its own counters and is the only one to
+//! write them, so recording is a few relaxed stores and does
+//! not share cache lines with other threads. Every call site
+//! keeps the counters of the threads passing through it, a
+//! thread finds its own through a small cache keyed by the
+//! address of the call site. A lock is only taken the first
+//! time a thread passes through a call site.
+//!
+//! When a thread exits its counters are added to the totals of
+//! the call site and handed to the next thread to come by, so
+//! memory grows with the threads running at once rather than
+//! with every thread ever started. `snapshot` includes the
+//! counts of exited threads.
+//!
+//! Nothing is recorded inside the transactional region, a
+//! write there would be rolled back with the abort it counts.
+//!
+//! `openmetrics` renders the counters in the OpenMetrics text
+//! format, which Prometheus scrapes.
+
+use std::cell::RefCell;
+use std::fmt::{self, Write};
+use std::panic::Location;
+use std::string::String;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::vec::Vec;
+
+use crate::AbortStatus;
+
+/// Abort status flags in the order they are counted.
+const FLAGS: [u32; 6] = [
+    AbortStatus::EXPLICIT,
+    AbortStatus::RETRY,
+    AbortStatus::CONFLICT,
+    AbortStatus::CAPACITY,
+    AbortStatus::DEBUG,
+    AbortStatus::NESTED,
+];
+
+/// Label values of `FLAGS`.
+const REASONS: [&str; 6] = [
+    "explicit", "retry", "conflict", "capacity", "debug", "nested",
+];
+
+/// The `Content-Type` to serve `openmetrics` with.
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Counters of one thread at one call site.
+pub(crate) struct Counters {
+    attempts: AtomicU64,
+    commits: AtomicU64,
+    aborts: AtomicU64,
+    fallbacks: AtomicU64,
+    flags: [AtomicU64; 6],
+    codes: [AtomicU64; 256],
+}
+
+impl Counters {
+    fn new() -> Counters {
+        Counters {
+            attempts: AtomicU64::new(0),
+            commits: AtomicU64::new(0),
+            aborts: AtomicU64::new(0),
+            fallbacks: AtomicU64::new(0),
+            flags: [const { AtomicU64::new(0) }; 6],
+            codes: [const { AtomicU64::new(0) }; 256],
+        }
+    }
+
+    #[inline]
+    pub(crate) fn attempt(&self) {
+        bump(&self.attempts);
+    }
+
+    #[inline]
+    pub(crate) fn commit(&self) {
+        bump(&self.commits);
+    }
+
+    #[inline]
+    pub(crate) fn fallback(&self) {
+        bump(&self.fallbacks);
+    }
+
+    pub(crate) fn abort(&self, status: AbortStatus) {
+        bump(&self.aborts);
+        for (counter, flag) in self.flags.iter().zip(FLAGS.iter()) {
+            if status.raw() & flag != 0 {
+                bump(counter);
+            }
+        }
+        if let Option::Some(code) = status.explicit_code() {
+            bump(&self.codes[code as usize]);
+        }
+    }
+
+    fn add_to(&self, counts: &mut Counts) {
+        counts.attempts += self.attempts.load(Ordering::Relaxed);
+        counts.commits += self.commits.load(Ordering::Relaxed);
+        counts.aborts += self.aborts.load(Ordering::Relaxed);
+        counts.fallbacks += self.fallbacks.load(Ordering::Relaxed);
+        for (sum, counter) in counts.flags.iter_mut().zip(self.flags.iter()) {
+            *sum += counter.load(Ordering::Relaxed);
+        }
+        for (sum, counter) in counts.codes.iter_mut().zip(self.codes.iter()) {
+            *sum += counter.load(Ordering::Relaxed);
+        }
+    }
+
+    /// Zeroes the counters for the next thread to own them.
+    fn reset(&self) {
+        let all = [&self.attempts, &self.commits, &self.aborts, &self.fallbacks];
+        for counter in all
+            .iter()
+            .cloned()
+            .chain(self.flags.iter())
+            .chain(self.codes.iter())
+        {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Only the owning thread writes its counters, so there is no
+/// need for a locked read-modify-write.
+#[inline(always)]
+fn bump(counter: &AtomicU64) {
+    counter.store(counter.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+}
+
+/// One call site, registered the first time any thread passes
+/// through it and kept for the life of the process.
+struct SiteCounters {
+    location: &'static Location<'static>,
+    slots: Mutex<Slots>,
+}
+
+struct Slots {
+    /// Counters owned by running threads.
+    live: Vec<&'static Counters>,
+    /// Zeroed counters released by exited threads.
+    free: Vec<&'static Counters>,
+    /// Sum of the counters of exited threads.
+    retired: Counts,
+}
+
+impl SiteCounters {
+    fn slots(&self) -> std::sync::MutexGuard<'_, Slots> {
+        self.slots
+            .lock()
+            .unwrap_or_else(|poison| poison.into_inner())
+    }
+
+    /// Hands out counters to a thread passing through for the
+    /// first time.
+    fn acquire(&self) -> &'static Counters {
+        let mut slots = self.slots();
+        let counters = match slots.free.pop() {
+            Option::Some(counters) => counters,
+            Option::None => std::boxed::Box::leak(std::boxed::Box::new(Counters::new())),
+        };
+        slots.live.push(counters);
+        counters
+    }
+
+    /// Takes back the counters of an exiting thread.
+    fn release(&self, counters: &'static Counters) {
+        let mut slots = self.slots();
+        slots.live.retain(|&live| !core::ptr::eq(live, counters));
+        counters.add_to(&mut slots.retired);
+        counters.reset();
+        slots.free.push(counters);
+    }
+
+    fn counts(&self) -> Counts {
+        let slots = self.slots();
+        let mut counts = slots.retired.clone();
+        for counters in slots.live.iter() {
+            counters.add_to(&mut counts);
+        }
+        counts
+    }
+}
+
+/// Every call site entered so far.
+static SITES: Mutex<Vec<&'static SiteCounters>> = Mutex::new(Vec::new());
+
+/// Entries in the per-thread cache of counters.
+const CACHE: usize = 32;
+
+/// The counters the calling thread holds.
+struct Held {
+    cache: [Option<(usize, &'static Counters)>; CACHE],
+    sites: Vec<(&'static SiteCounters, &'static Counters)>,
+}
+
+impl Held {
+    #[cold]
+    fn find(&mut self, location: &'static Location<'static>) -> &'static Counters {
+        for &(site, counters) in self.sites.iter() {
+            if core::ptr::eq(site.location, location) {
+                return counters;
+            }
+        }
+        let site = site(location);
+        let counters = site.acquire();
+        self.sites.push((site, counters));
+        counters
+    }
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        for &(site, counters) in self.sites.iter() {
+            site.release(counters);
+        }
+    }
+}
+
+thread_local! {
+    static HELD: RefCell<Held> = const {
+        RefCell::new(Held {
+            cache: [None; CACHE],
+            sites: Vec::new(),
+        })
+    };
+}
+
+/// Returns the counters of the calling thread for `location`.
+///
+/// `None` once the thread is being torn down.
+#[inline]
+pub(crate) fn counters(location: &'static Location<'static>) -> Option<&'static Counters> {
+    let key = location as *const Location as usize;
+    HELD.try_with(|held| {
+        let mut held = held.borrow_mut();
+        let slot = (key >> 3) % CACHE;
+        match held.cache[slot] {
+            Option::Some((cached, counters)) if cached == key => counters,
+            _ => {
+                let counters = held.find(location);
+                held.cache[slot] = Option::Some((key, counters));
+                counters
+            }
+        }
+    })
+    .ok()
+}
+
+/// The entry of `location`, registering it on first use.
+#[cold]
+fn site(location: &'static Location<'static>) -> &'static SiteCounters {
+    let mut sites = SITES.lock().unwrap_or_else(|poison| poison.into_inner());
+    if let Option::Some(site) = sites
+        .iter()
+        .find(|site| core::ptr::eq(site.location, location))
+    {
+        return site;
+    }
+    let site: &'static SiteCounters = std::boxed::Box::leak(std::boxed::Box::new(SiteCounters {
+        location,
+        slots: Mutex::new(Slots {
+            live: Vec::new(),
+            free: Vec::new(),
+            retired: Counts::new(),
+        }),
+    }));
+    sites.push(site);
+    site
+}
+
+/// Totals for one call site, or for all of them.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Counts {
+    attempts: u64,
+    commits: u64,
+    aborts: u64,
+    fallbacks: u64,
+    flags: [u64; 6],
+    codes: [u64; 256],
+}
+
+impl Counts {
+    fn new() -> Counts {
+        Counts {
+            attempts: 0,
+            commits: 0,
+            aborts: 0,
+            fallbacks: 0,
+            flags: [0; 6],
+            codes: [0; 256],
+        }
+    }
+
+    /// Hardware transactions started, including retries.
+    #[inline]
+    pub fn attempts(&self) -> u64 {
+        self.attempts
+    }
+
+    /// Hardware transactions that committed.
+    #[inline]
+    pub fn commits(&self) -> u64 {
+        self.commits
+    }
+
+    /// Hardware transactions that aborted, for any reason.
+    #[inline]
+    pub fn aborts(&self) -> u64 {
+        self.aborts
+    }
+
+    /// Critical sections that ran under a fallback lock.
+    #[inline]
+    pub fn fallbacks(&self) -> u64 {
+        self.fallbacks
+    }
+
+    /// Aborts whose status had any of the bits in `flag` set,
+    /// e.g. `AbortStatus::CAPACITY`.
+    ///
+    /// Aborts usually set more than one flag, so these do not
+    /// add up to `aborts`.
+    pub fn aborts_with(&self, flag: u32) -> u64 {
+        self.flags
+            .iter()
+            .zip(FLAGS.iter())
+            .filter(|&(_, bit)| flag & bit != 0)
+            .map(|(count, _)| count)
+            .sum()
+    }
+
+    /// Explicit aborts made with `code`.
+    #[inline]
+    pub fn explicit_code(&self, code: u8) -> u64 {
+        self.codes[code as usize]
+    }
+}
+
+impl Default for Counts {
+    fn default() -> Counts {
+        Counts::new()
+    }
+}
+
+/// Counters merged from every thread.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    total: Counts,
+    sites: Vec<(&'static Location<'static>, Counts)>,
+}
+
+impl Snapshot {
+    /// Totals over every call site.
+    #[inline]
+    pub fn total(&self) -> &Counts {
+        &self.total
+    }
+
+    /// Totals for each call site which was entered at least once.
+    pub fn sites(&self) -> impl Iterator<Item = (&'static Location<'static>, &Counts)> {
+        self.sites
+            .iter()
+            .map(|(location, counts)| (*location, counts))
+    }
+
+    /// Totals for the call site at `file` and `line`, summed
+    /// over every column.
+    pub fn site(&self, file: &str, line: u32) -> Option<Counts> {
+        let mut found = Option::None;
+        for (location, counts) in self.sites() {
+            if location.file() == file && location.line() == line {
+                let sum = found.get_or_insert_with(Counts::new);
+                merge(sum, counts);
+            }
+        }
+        found
+    }
+}
+
+fn merge(sum: &mut Counts, counts: &Counts) {
+    sum.attempts += counts.attempts;
+    sum.commits += counts.commits;
+    sum.aborts += counts.aborts;
+    sum.fallbacks += counts.fallbacks;
+    for (sum, count) in sum.flags.iter_mut().zip(counts.flags.iter()) {
+        *sum += count;
+    }
+    for (sum, count) in sum.codes.iter_mut().zip(counts.codes.iter()) {
+        *sum += count;
+    }
+}
+
+/// Merges the counters of every thread.
+///
+/// Threads keep counting while this runs, so the snapshot is
+/// not taken at a single instant.
+pub fn snapshot() -> Snapshot {
+    let sites: Vec<&'static SiteCounters> = SITES
+        .lock()
+        .unwrap_or_else(|poison| poison.into_inner())
+        .clone();
+    collect(sites.into_iter().map(|site| (site.location, site.counts())))
+}
+
+/// The counters of the calling thread alone.
+pub fn thread_snapshot() -> Snapshot {
+    let held: Vec<(&'static Location<'static>, Counts)> = HELD
+        .try_with(|held| {
+            held.borrow()
+                .sites
+                .iter()
+                .map(|&(site, counters)| {
+                    let mut counts = Counts::new();
+                    counters.add_to(&mut counts);
+                    (site.location, counts)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    collect(held.into_iter())
+}
+
+/// Sums the counts of each call site, `Location`s equal in
+/// value are one site even when they are separate copies.
+fn collect<I>(counts: I) -> Snapshot
+where
+    I: Iterator<Item = (&'static Location<'static>, Counts)>,
+{
+    let mut snapshot = Snapshot {
+        total: Counts::new(),
+        sites: Vec::new(),
+    };
+    for (location, counts) in counts {
+        merge(&mut snapshot.total, &counts);
+        match snapshot
+            .sites
+            .iter_mut()
+            .find(|(known, _)| *known == location)
+        {
+            Option::Some((_, sum)) => merge(sum, &counts),
+            Option::None => snapshot.sites.push((location, counts)),
+        }
+    }
+    snapshot
+}
+
+/// Renders the counters of every thread in the OpenMetrics
+/// text format.
+pub fn openmetrics() -> String {
+    let mut out = String::new();
+    snapshot()
+        .write_openmetrics(&mut out)
+        .expect("writing to a String cannot fail");
+    out
+}
+
+impl Snapshot {
+    /// Writes the counters in the OpenMetrics text format,
+    /// terminated by `# EOF`.
+    ///
+    /// Every series carries a `site` label holding the call site
+    /// as `file:line:column`. Aborts are reported once in
+    /// `rtm_aborts`, and again by reason and by explicit code.
+    /// A single abort usually sets more than one reason, so the
+    /// reasons do not sum to the aborts. Codes that were never
+    /// used are left out.
+    pub fn write_openmetrics<W: Write>(&self, out: &mut W) -> fmt::Result {
+        family(
+            out,
+            "rtm_attempts",
+            "Hardware transactions started, including retries.",
+        )?;
+        for (location, counts) in self.sites() {
+            sample(out, "rtm_attempts", location, None, counts.attempts())?;
+        }
+        family(out, "rtm_commits", "Hardware transactions committed.")?;
+        for (location, counts) in self.sites() {
+            sample(out, "rtm_commits", location, None, counts.commits())?;
+        }
+        family(
+            out,
+            "rtm_fallbacks",
+            "Critical sections run under a fallback lock.",
+        )?;
+        for (location, counts) in self.sites() {
+            sample(out, "rtm_fallbacks", location, None, counts.fallbacks())?;
+        }
+        family(out, "rtm_aborts", "Hardware transactions aborted.")?;
+        for (location, counts) in self.sites() {
+            sample(out, "rtm_aborts", location, None, counts.aborts())?;
+        }
+        family(
+            out,
+            "rtm_abort_reasons",
+            "Hardware transactions aborted, by flag set in the abort status.",
+        )?;
+        for (location, counts) in self.sites() {
+            for (reason, flag) in REASONS.iter().zip(FLAGS.iter()) {
+                let label = ("reason", Label::Str(reason));
+                sample(
+                    out,
+                    "rtm_abort_reasons",
+                    location,
+                    Some(label),
+                    counts.aborts_with(*flag),
+                )?;
+            }
+        }
+        family(
+            out,
+            "rtm_explicit_aborts",
+            "Hardware transactions aborted explicitly, by abort code.",
+        )?;
+        for (location, counts) in self.sites() {
+            for code in 0..=255u8 {
+                let count = counts.explicit_code(code);
+                if count != 0 {
+                    let label = ("code", Label::Code(code));
+                    sample(out, "rtm_explicit_aborts", location, Some(label), count)?;
+                }
+            }
+        }
+        out.write_str("# EOF\n")
+    }
+}
+
+/// Value of the optional second label of a sample.
+enum Label<'a> {
+    Str(&'a str),
+    Code(u8),
+}
+
+fn family<W: Write>(out: &mut W, name: &str, help: &str) -> fmt::Result {
+    writeln!(out, "# TYPE {} counter", name)?;
+    writeln!(out, "# HELP {} {}", name, help)
+}
+
+fn sample<W: Write>(
+    out: &mut W,
+    name: &str,
+    location: &Location,
+    label: Option<(&str, Label)>,
+    value: u64,
+) -> fmt::Result {
+    write!(out, "{}_total{{site=\"", name)?;
+    escape(out, location.file())?;
+    write!(out, ":{}:{}\"", location.line(), location.column())?;
+    match label {
+        Option::Some((key, Label::Str(value))) => write!(out, ",{}=\"{}\"", key, value)?,
+        Option::Some((key, Label::Code(code))) => write!(out, ",{}=\"{}\"", key, code)?,
+        Option::None => {}
+    }
+    writeln!(out, "}} {}", value)
+}
+
+/// Escapes a label value, file names may hold backslashes.
+fn escape<W: Write>(out: &mut W, value: &str) -> fmt::Result {
+    for c in value.chars() {
+        match c {
+            '\\' => out.write_str("\\\\")?,
+            '"' => out.write_str("\\\"")?,
+            '\n' => out.write_str("\\n")?,
+            c => out.write_char(c)?,
+        }
+    }
+    Ok(())
+}
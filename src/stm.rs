@@ -0,0 +1,403 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Software transactional memory.
+//!
+//! A TL2 style STM (Dice, Shalev and Shavit, 2006) for hosts
+//! without RTM. Unlike a fallback lock, transactions that touch
+//! disjoint data run in parallel.
+//!
+//! * A global version clock is sampled when a transaction
+//!   starts, its read version.
+//! * Memory is split into stripes by cache line, each guarded by
+//!   a versioned lock holding the clock value of the last commit
+//!   that wrote to it.
+//! * Reads check the stripe is unlocked and no newer than the
+//!   read version, so a transaction only ever observes a
+//!   consistent snapshot (opacity), even one that will abort.
+//! * Writes are buffered and only written back at commit, after
+//!   locking the written stripes and validating the read set.
+//!
+//! Data is accessed through `TxCell`s, read and written with a
+//! `Tx` handed to the closure.
+//!
+//!# Hybrid mode
+//!
+//! `hybrid` first runs the same closure as a hardware
+//! transaction and only falls back to the STM once that keeps
+//! aborting, so a thread on the slow path does not stop the
+//! others from committing in hardware. The two stay consistent
+//! as follows.
+//!
+//! * Hardware transactions read the versioned lock of every
+//!   stripe they touch and abort if it is held. Holding it on
+//!   the cache line means a software commit locking the stripe
+//!   later aborts them too.
+//! * Writing hardware transactions read the global clock and
+//!   stamp the stripes they write with the next version, so a
+//!   software transaction that already read one fails
+//!   validation. Reading the clock subscribes to it, every
+//!   software commit aborts the running hardware writers. A
+//!   software reader that meets such a version moves the clock
+//!   forward before retrying.
+
+use alloc::vec::Vec;
+use core::sync::atomic::{fence, AtomicU64, Ordering};
+
+use crate::elision::Site;
+use crate::{AbortStatus, CacheAligned, Fixed, RetryPolicy, TxCell};
+
+/// Number of versioned locks, a power of two.
+const STRIPES: usize = 1024;
+
+/// Set in a stripe while a commit writes to it, the version is
+/// kept in the remaining bits.
+const LOCKED: u64 = 1;
+
+static CLOCK: AtomicU64 = AtomicU64::new(0);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const STRIPE: CacheAligned<AtomicU64> = CacheAligned::new(AtomicU64::new(0));
+/// Each versioned lock is on its own cache line.
+static TABLE: [CacheAligned<AtomicU64>; STRIPES] = [STRIPE; STRIPES];
+
+/// Returns the index of the stripe guarding `addr`.
+#[inline]
+fn stripe(addr: usize) -> usize {
+    let line = addr >> 6;
+    (line ^ (line >> 10) ^ (line >> 20)) & (STRIPES - 1)
+}
+
+/// Why a transaction stopped, returned from the closure with `?`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Aborted(AbortStatus);
+
+impl Aborted {
+    /// The status the transaction aborted with. Conflicts carry
+    /// the conflict and retry flags, like a hardware conflict.
+    #[inline]
+    pub fn status(&self) -> AbortStatus {
+        self.0
+    }
+
+    #[inline]
+    fn conflict() -> Aborted {
+        Aborted(AbortStatus::from_raw(
+            AbortStatus::CONFLICT | AbortStatus::RETRY,
+        ))
+    }
+}
+
+/// A buffered write of `len` bytes at `addr`, kept in the write
+/// buffer from `offset`.
+struct Write {
+    addr: usize,
+    offset: usize,
+    len: usize,
+}
+
+/// A running transaction.
+pub struct Tx {
+    hardware: bool,
+    version: u64,
+    reads: Vec<usize>,
+    writes: Vec<Write>,
+    buffer: Vec<u8>,
+    locked: Vec<(usize, u64)>,
+}
+
+impl Tx {
+    fn new(hardware: bool) -> Tx {
+        Tx {
+            hardware,
+            version: 0,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            buffer: Vec::new(),
+            locked: Vec::new(),
+        }
+    }
+
+    fn begin(&mut self) {
+        self.reads.clear();
+        self.writes.clear();
+        self.buffer.clear();
+        self.version = CLOCK.load(Ordering::Acquire);
+    }
+
+    /// Reads `cell`, seeing the writes this transaction made.
+    ///
+    /// Fails when `cell` changed since the transaction started,
+    /// the error must be returned from the closure which is then
+    /// run again.
+    pub fn read<T: Copy>(&mut self, cell: &TxCell<T>) -> Result<T, Aborted> {
+        let addr = cell.as_ptr() as usize;
+        if self.hardware {
+            subscribe(stripe(addr));
+            return Ok(unsafe { cell.get() });
+        }
+        if let Option::Some(write) = self.writes.iter().find(|write| write.addr == addr) {
+            let bytes = self.buffer[write.offset..].as_ptr();
+            return Ok(unsafe { (bytes as *const T).read_unaligned() });
+        }
+        let index = stripe(addr);
+        let lock = &TABLE[index];
+
+        #[cfg(feature = "emulated")]
+        crate::emulated::enter();
+
+        let before = lock.load(Ordering::Acquire);
+        // a racing commit is caught by the version check below
+        let value = unsafe { cell.get() };
+        fence(Ordering::Acquire);
+        let after = lock.load(Ordering::Relaxed);
+
+        #[cfg(feature = "emulated")]
+        crate::emulated::exit();
+
+        if before & LOCKED != 0 || before != after {
+            return Err(Aborted::conflict());
+        }
+        if before >> 1 > self.version {
+            // stamped by a hardware transaction, which does not
+            // move the clock itself
+            CLOCK.fetch_max(before >> 1, Ordering::AcqRel);
+            return Err(Aborted::conflict());
+        }
+        self.reads.push(index);
+        Ok(value)
+    }
+
+    /// Writes `value` to `cell` when the transaction commits.
+    pub fn write<T: Copy>(&mut self, cell: &TxCell<T>, value: T) {
+        let addr = cell.as_ptr() as usize;
+        if self.hardware {
+            let index = stripe(addr);
+            subscribe(index);
+            let version = CLOCK.load(Ordering::Relaxed) + 1;
+            TABLE[index].store(version << 1, Ordering::Relaxed);
+            unsafe { cell.set(value) };
+            return;
+        }
+        let offset = match self.writes.iter().find(|write| write.addr == addr) {
+            Option::Some(write) => write.offset,
+            Option::None => {
+                let offset = self.buffer.len();
+                let len = core::mem::size_of::<T>();
+                self.buffer.resize(offset + len, 0);
+                self.writes.push(Write { addr, offset, len });
+                offset
+            }
+        };
+        let bytes = self.buffer[offset..].as_mut_ptr();
+        unsafe { (bytes as *mut T).write_unaligned(value) };
+    }
+
+    /// Aborts the transaction with an explicit `code`, the
+    /// result must be returned from the closure.
+    #[inline]
+    pub fn abort(&self, code: u8) -> Aborted {
+        Aborted(AbortStatus::from_code(code))
+    }
+
+    fn commit(&mut self) -> Result<(), Aborted> {
+        if self.writes.is_empty() {
+            // every read was validated against the read version
+            return Ok(());
+        }
+
+        #[cfg(feature = "emulated")]
+        crate::emulated::enter();
+
+        let out = self.write_back();
+
+        #[cfg(feature = "emulated")]
+        crate::emulated::exit();
+
+        out
+    }
+
+    fn write_back(&mut self) -> Result<(), Aborted> {
+        let mut stripes: Vec<usize> = self.writes.iter().map(|write| stripe(write.addr)).collect();
+        stripes.sort_unstable();
+        stripes.dedup();
+        self.locked.clear();
+        for index in stripes {
+            let lock = &TABLE[index];
+            let current = lock.load(Ordering::Relaxed);
+            if current & LOCKED != 0
+                || lock
+                    .compare_exchange(
+                        current,
+                        current | LOCKED,
+                        Ordering::Acquire,
+                        Ordering::Relaxed,
+                    )
+                    .is_err()
+            {
+                self.unlock();
+                return Err(Aborted::conflict());
+            }
+            self.locked.push((index, current));
+        }
+        let version = CLOCK.fetch_add(1, Ordering::AcqRel) + 1;
+        // hardware commits stamp stripes without moving the clock,
+        // so the read set is validated even when no other software
+        // commit happened
+        if !self.validate() {
+            self.unlock();
+            return Err(Aborted::conflict());
+        }
+        for write in self.writes.iter() {
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    self.buffer[write.offset..].as_ptr(),
+                    write.addr as *mut u8,
+                    write.len,
+                )
+            };
+        }
+        for &(index, _) in self.locked.iter() {
+            TABLE[index].store(version << 1, Ordering::Release);
+        }
+        Ok(())
+    }
+
+    /// Checks no stripe that was read has been written since the
+    /// transaction started.
+    fn validate(&self) -> bool {
+        self.reads.iter().all(|&index| {
+            let current = TABLE[index].load(Ordering::Acquire);
+            let ours = current & LOCKED != 0
+                && self
+                    .locked
+                    .binary_search_by_key(&index, |&(locked, _)| locked)
+                    .is_ok();
+            (current & LOCKED == 0 || ours) && current >> 1 <= self.version
+        })
+    }
+
+    /// Releases the locks taken by a failed commit, leaving
+    /// their versions untouched.
+    fn unlock(&mut self) {
+        for &(index, previous) in self.locked.iter() {
+            TABLE[index].store(previous, Ordering::Release);
+        }
+        self.locked.clear();
+    }
+}
+
+/// Runs `lambda` as a software transaction.
+///
+/// Conflicts are retried until the transaction commits, only an
+/// explicit abort made with `Tx::abort` is returned.
+pub fn transaction<T, F>(lambda: F) -> Result<T, AbortStatus>
+where
+    F: FnMut(&mut Tx) -> Result<T, Aborted>,
+{
+    transaction_retry(lambda, Conflicts)
+}
+
+/// Runs `lambda` as a software transaction, consulting `policy`
+/// after every abort, explicit ones included.
+pub fn transaction_retry<T, F, P>(mut lambda: F, mut policy: P) -> Result<T, AbortStatus>
+where
+    F: FnMut(&mut Tx) -> Result<T, Aborted>,
+    P: RetryPolicy,
+{
+    let mut tx = Tx::new(false);
+    let mut attempt = 0usize;
+    loop {
+        tx.begin();
+        let status = match lambda(&mut tx) {
+            Ok(output) => match tx.commit() {
+                Ok(()) => return Ok(output),
+                Err(aborted) => aborted.0,
+            },
+            Err(aborted) => aborted.0,
+        };
+        attempt += 1;
+        if !policy.retry(attempt, status) {
+            return Err(status);
+        }
+    }
+}
+
+/// Runs `lambda` as a hardware transaction, making up to
+/// `DEFAULT_RETRIES` retries, then as a software transaction.
+///
+/// An explicit abort made with `Tx::abort` is returned from
+/// either path. On processors without RTM this is the same as
+/// `transaction`.
+#[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+pub fn hybrid<T, F>(lambda: F) -> Result<T, AbortStatus>
+where
+    F: FnMut(&mut Tx) -> Result<T, Aborted>,
+{
+    hybrid_retry(lambda, Fixed(crate::DEFAULT_RETRIES))
+}
+
+/// Runs `lambda` as a hardware transaction until `policy` gives
+/// up, then as a software transaction.
+#[cfg_attr(any(feature = "stats", feature = "tracing"), track_caller)]
+pub fn hybrid_retry<T, F, P>(mut lambda: F, policy: P) -> Result<T, AbortStatus>
+where
+    F: FnMut(&mut Tx) -> Result<T, Aborted>,
+    P: RetryPolicy,
+{
+    let site = Site::caller();
+    let mut tx = Tx::new(true);
+    let outcome = crate::elision::elide_with(
+        site,
+        policy,
+        || true,
+        || match lambda(&mut tx) {
+            Ok(output) => output,
+            Err(aborted) => {
+                // reads never fail in hardware, so this is `Tx::abort`
+                crate::try_abort(aborted.0.explicit_code().unwrap_or(0));
+                unreachable!("try_abort returned while a transaction was active")
+            }
+        },
+    );
+    match outcome {
+        Ok(output) => Ok(output),
+        Err(status) if status.explicit() && !status.lock_busy() => Err(status),
+        Err(status) => {
+            site.fallback(status);
+            transaction(lambda)
+        }
+    }
+}
+
+/// Subscribes a hardware transaction to a versioned lock,
+/// aborting if a software commit holds it.
+#[inline]
+fn subscribe(index: usize) {
+    if TABLE[index].load(Ordering::Relaxed) & LOCKED != 0 {
+        crate::abort_with::<{ crate::LOCK_BUSY }>();
+    }
+}
+
+/// Retries every abort that is not explicit.
+struct Conflicts;
+
+impl RetryPolicy for Conflicts {
+    #[inline]
+    fn retry(&mut self, _: usize, status: AbortStatus) -> bool {
+        !status.explicit()
+    }
+}
@@ -0,0 +1,135 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! `tracing` integration.
+//!
+//! Enabled with the `tracing` feature. Every hardware attempt
+//! runs inside an `attempt` span, each abort emits an event at
+//! `DEBUG` within it and every critical section that runs under
+//! a fallback lock emits one at `INFO`, all with the `rtm`
+//! target. A `transaction` over data spanning more cache lines
+//! than a write set can hold is warned about once per call site.
+//!
+//! Nothing is emitted inside the transactional region, a
+//! subscriber is free to allocate or write to a file, any of
+//! which aborts the transaction. Spans are entered before an
+//! attempt starts and left once it has committed or aborted,
+//! events are only emitted once the attempt has aborted.
+//!
+//! Aborts can happen millions of times a second in a hot loop,
+//! so at most `BURST` events are emitted each second. The rest
+//! are dropped and counted, the count is reported by the first
+//! event of the next second.
+
+use std::panic::Location;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::Instant;
+
+use crate::AbortStatus;
+
+/// Events emitted per second, across all threads.
+const BURST: u32 = 100;
+
+static START: OnceLock<Instant> = OnceLock::new();
+static SECOND: AtomicU64 = AtomicU64::new(0);
+static EMITTED: AtomicU32 = AtomicU32::new(0);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// Sites already warned about by `oversized`.
+static OVERSIZED: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+/// Returns `true` if the rate limit allows another event.
+fn allow() -> bool {
+    let second = START.get_or_init(Instant::now).elapsed().as_secs();
+    let current = SECOND.load(Ordering::Relaxed);
+    if second > current
+        && SECOND
+            .compare_exchange(current, second, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    {
+        EMITTED.store(0, Ordering::Relaxed);
+        let dropped = DROPPED.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            tracing::warn!(target: "rtm", dropped, "rate limited transaction events");
+        }
+    }
+    if EMITTED.fetch_add(1, Ordering::Relaxed) < BURST {
+        true
+    } else {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+        false
+    }
+}
+
+/// Enters the span of hardware attempt number `attempt`.
+#[inline]
+pub(crate) fn span(
+    location: &'static Location<'static>,
+    attempt: usize,
+) -> tracing::span::EnteredSpan {
+    tracing::debug_span!(target: "rtm", "attempt", site = %location, attempt).entered()
+}
+
+/// Reports the abort of hardware attempt number `attempt`.
+#[inline]
+pub(crate) fn abort(location: &'static Location<'static>, attempt: usize, status: AbortStatus) {
+    if tracing::enabled!(target: "rtm", tracing::Level::DEBUG) && allow() {
+        tracing::debug!(
+            target: "rtm",
+            site = %location,
+            attempt,
+            code = ?status.code(),
+            status = status.raw(),
+            "transaction aborted"
+        );
+    }
+}
+
+/// Reports a critical section about to run under a fallback
+/// lock, `status` is that of the last abort.
+#[inline]
+pub(crate) fn fallback(location: &'static Location<'static>, status: AbortStatus) {
+    if tracing::enabled!(target: "rtm", tracing::Level::INFO) && allow() {
+        tracing::info!(
+            target: "rtm",
+            site = %location,
+            code = ?status.code(),
+            status = status.raw(),
+            "transaction fell back to lock"
+        );
+    }
+}
+
+/// Warns, once per site, that the data of a transaction spans
+/// more cache lines than a write set can hold.
+#[cold]
+pub(crate) fn oversized(location: &'static Location<'static>, name: &'static str, lines: usize) {
+    let site = location as *const Location as usize;
+    let mut warned = OVERSIZED.lock().unwrap_or_else(PoisonError::into_inner);
+    if warned.contains(&site) {
+        return;
+    }
+    warned.push(site);
+    drop(warned);
+    tracing::warn!(
+        target: "rtm",
+        site = %location,
+        data = name,
+        lines,
+        "transaction data spans more cache lines than the write set holds"
+    );
+}
@@ -0,0 +1,164 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The software undo log.
+//!
+//! Writes made through `TxCell` and `TxVec` are logged while a
+//! software transaction is open on the thread, so they can be
+//! rolled back. Software transactions are opened by the fallback
+//! path of `transaction`, and by the `emulated` backend for
+//! every emulated hardware transaction.
+//!
+//! Under RTM nothing is logged, the hardware keeps the old
+//! values itself. Without the `std` feature there is no log, the
+//! fallback path runs without one and cannot be aborted.
+
+#[cfg(feature = "std")]
+use std::cell::{Cell, RefCell};
+#[cfg(feature = "std")]
+use std::mem::MaybeUninit;
+#[cfg(feature = "std")]
+use std::panic::{self, AssertUnwindSafe};
+
+#[cfg(feature = "std")]
+use crate::AbortStatus;
+
+/// Unwinding payload of an abort, carries the abort status.
+#[cfg(feature = "std")]
+pub(crate) struct Abort(pub(crate) u32);
+
+/// A logged write, enough to put the old bytes back.
+///
+/// The bytes are kept as `MaybeUninit`, the old value may be an
+/// unwritten `TxVec` slot or hold padding, neither of which may
+/// be read as a `u8`.
+#[cfg(feature = "std")]
+struct Undo {
+    addr: *mut u8,
+    bytes: Vec<MaybeUninit<u8>>,
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    /// How many software transactions are open on this thread.
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+
+    static LOG: RefCell<Vec<Undo>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Returns `true` while a software transaction is open.
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn active() -> bool {
+    DEPTH.with(Cell::get) > 0
+}
+
+/// Records the `len` bytes at `addr` so an abort can restore
+/// them. Does nothing outside of a software transaction.
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn log_write(addr: *mut u8, len: usize) {
+    if !active() {
+        return;
+    }
+    let mut bytes = Vec::with_capacity(len);
+    unsafe {
+        std::ptr::copy_nonoverlapping(addr as *const MaybeUninit<u8>, bytes.as_mut_ptr(), len);
+        bytes.set_len(len);
+    }
+    LOG.with(|log| log.borrow_mut().push(Undo { addr, bytes }));
+}
+
+/// Opens a software transaction, returning the mark to roll
+/// back to.
+#[cfg(feature = "std")]
+pub(crate) fn begin() -> usize {
+    DEPTH.with(|depth| depth.set(depth.get() + 1));
+    LOG.with(|log| log.borrow().len())
+}
+
+/// Closes a software transaction keeping its writes. The log is
+/// only dropped once the outermost one commits, the writes of
+/// a nested one still belong to its parent.
+#[cfg(feature = "std")]
+pub(crate) fn commit() {
+    let depth = DEPTH.with(|depth| {
+        depth.set(depth.get() - 1);
+        depth.get()
+    });
+    if depth == 0 {
+        LOG.with(|log| log.borrow_mut().clear());
+    }
+}
+
+/// Closes a software transaction undoing every write logged
+/// since `mark`.
+#[cfg(feature = "std")]
+pub(crate) fn rollback(mark: usize) {
+    let undo = LOG.with(|log| log.borrow_mut().split_off(mark));
+    for entry in undo.iter().rev() {
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                entry.bytes.as_ptr(),
+                entry.addr as *mut MaybeUninit<u8>,
+                entry.bytes.len(),
+            )
+        };
+    }
+    DEPTH.with(|depth| depth.set(depth.get() - 1));
+}
+
+/// Aborts the open software transaction with an explicit `code`.
+#[cfg(feature = "std")]
+pub(crate) fn abort(code: u8) -> ! {
+    panic::resume_unwind(Box::new(Abort(AbortStatus::from_code(code).raw())))
+}
+
+/// Runs `body` as a software transaction.
+///
+/// An abort rolls back the logged writes and returns its status,
+/// a panic rolls them back and keeps unwinding.
+#[cfg(feature = "std")]
+pub(crate) fn run<F>(body: F) -> Result<(), AbortStatus>
+where
+    F: FnOnce(),
+{
+    let mark = begin();
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(()) => {
+            commit();
+            Ok(())
+        }
+        Err(payload) => {
+            rollback(mark);
+            match payload.downcast::<Abort>() {
+                Ok(abort) => Err(AbortStatus::from_raw(abort.0)),
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        }
+    }
+}
+
+/// Without `std` there is nothing to log, `body` simply runs.
+#[cfg(not(feature = "std"))]
+#[inline(always)]
+pub(crate) fn run<F>(body: F) -> Result<(), crate::AbortStatus>
+where
+    F: FnOnce(),
+{
+    body();
+    Ok(())
+}
@@ -0,0 +1,216 @@
+/*
+Copyright 2017 William Cody Laeder
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A fixed capacity vector for transactional data.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::mem::MaybeUninit;
+
+use crate::TxCell;
+
+/// A vector of `Copy` values that is read and written inside
+/// transactions.
+///
+/// The length and every slot are `TxCell`s, so writes are plain
+/// memory operations under RTM and are logged on the fallback
+/// path and under the `emulated` feature, an abort restores both
+/// the values and the length.
+///
+/// The capacity is fixed when the vector is created. Growing it
+/// would call the allocator, which aborts any hardware
+/// transaction, so `push` fails once the vector is full.
+///
+/// Like `TxCell::get` and `TxCell::set`, every method that reads
+/// or writes the values or the length is `unsafe`: no other
+/// thread may write the vector while it runs, or access it at
+/// all when the method writes. This holds inside a transaction or
+/// fallback of this crate that all such threads go through, or
+/// when no other thread can reach the vector.
+pub struct TxVec<T: Copy> {
+    len: TxCell<usize>,
+    slots: Box<[TxCell<MaybeUninit<T>>]>,
+}
+
+impl<T: Copy> TxVec<T> {
+    /// Creates an empty vector holding at most `capacity` values.
+    pub fn with_capacity(capacity: usize) -> TxVec<T> {
+        TxVec {
+            len: TxCell::new(0),
+            slots: (0..capacity)
+                .map(|_| TxCell::new(MaybeUninit::uninit()))
+                .collect(),
+        }
+    }
+
+    /// The most values the vector can hold.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// The number of values in the vector.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may write the vector meanwhile, see the
+    /// type docs.
+    #[inline]
+    pub unsafe fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Returns `true` if the vector holds no values.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may write the vector meanwhile, see the
+    /// type docs.
+    #[inline]
+    pub unsafe fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if `push` would fail.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may write the vector meanwhile, see the
+    /// type docs.
+    #[inline]
+    pub unsafe fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// Reads the value at `index`.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may write the vector meanwhile, see the
+    /// type docs.
+    #[inline]
+    pub unsafe fn get(&self, index: usize) -> Option<T> {
+        if index < self.len() {
+            Some(self.slots[index].get().assume_init())
+        } else {
+            None
+        }
+    }
+
+    /// Writes the value at `index`.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may access the vector meanwhile, see the
+    /// type docs.
+    #[inline]
+    pub unsafe fn set(&self, index: usize, value: T) {
+        let len = self.len();
+        assert!(
+            index < len,
+            "index {} out of bounds for length {}",
+            index,
+            len
+        );
+        self.slots[index].set(MaybeUninit::new(value));
+    }
+
+    /// Appends a value, handing it back if the vector is full.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may access the vector meanwhile, see the
+    /// type docs.
+    #[inline]
+    pub unsafe fn push(&self, value: T) -> Result<(), T> {
+        let len = self.len();
+        if len == self.capacity() {
+            return Err(value);
+        }
+        self.slots[len].set(MaybeUninit::new(value));
+        self.len.set(len + 1);
+        Ok(())
+    }
+
+    /// Removes the last value.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may access the vector meanwhile, see the
+    /// type docs.
+    #[inline]
+    pub unsafe fn pop(&self) -> Option<T> {
+        let len = self.len();
+        if len == 0 {
+            return None;
+        }
+        self.len.set(len - 1);
+        Some(self.slots[len - 1].get().assume_init())
+    }
+
+    /// Shortens the vector to `len` values, does nothing if it
+    /// is not longer.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may access the vector meanwhile, see the
+    /// type docs.
+    #[inline]
+    pub unsafe fn truncate(&self, len: usize) {
+        if len < self.len() {
+            self.len.set(len);
+        }
+    }
+
+    /// Removes every value.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may access the vector meanwhile, see the
+    /// type docs.
+    #[inline]
+    pub unsafe fn clear(&self) {
+        self.truncate(0);
+    }
+
+    /// Iterates over copies of the values.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may write the vector until the iterator is
+    /// dropped, see the type docs.
+    pub unsafe fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.slots[..self.len()]
+            .iter()
+            .map(|slot| unsafe { slot.get().assume_init() })
+    }
+}
+
+impl<T: Copy> From<Vec<T>> for TxVec<T> {
+    /// Moves the values of `vec` over, keeping its capacity.
+    fn from(vec: Vec<T>) -> TxVec<T> {
+        let out = TxVec::with_capacity(vec.capacity());
+        for value in vec {
+            // `out` is not shared yet.
+            let _ = unsafe { out.push(value) };
+        }
+        out
+    }
+}
@@ -0,0 +1,74 @@
+extern crate rtm;
+
+use rtm::{abort_with, try_abort, AbortStatus, Fallback, TransactionError, TxCell};
+
+/// Runs `lambda` as a transaction until it aborts explicitly,
+/// the hardware may abort for unrelated reasons (interrupts).
+fn explicit_abort<F>(data: &mut TxCell<u64>, lambda: F) -> AbortStatus
+where
+    F: Fn(&mut TxCell<u64>),
+{
+    loop {
+        match rtm::transaction(data, &lambda, Fallback::None) {
+            Err(status) if status.explicit() => return status,
+            Err(_) => continue,
+            Ok(_) => panic!("transaction committed"),
+        }
+    }
+}
+
+#[test]
+fn abort_outside_transaction_is_ignored() {
+    assert!(!try_abort(0));
+    assert!(!try_abort(255));
+    abort_with::<7>();
+    rtm::abort(7);
+}
+
+#[test]
+#[cfg_attr(not(feature = "emulated"), ignore = "needs RTM")]
+fn abort_with_reports_code() {
+    let mut data = TxCell::new(0u64);
+    let status = explicit_abort(&mut data, |data| unsafe {
+        data.set(1);
+        abort_with::<42>();
+    });
+    assert_eq!(status.explicit_code(), Some(42));
+    assert_eq!(*data.get_mut(), 0);
+}
+
+#[test]
+#[cfg_attr(not(feature = "emulated"), ignore = "needs RTM")]
+fn try_abort_reports_every_code() {
+    for code in 0..=255u8 {
+        let mut data = TxCell::new(0u64);
+        let status = explicit_abort(&mut data, |data| unsafe {
+            data.set(1);
+            try_abort(code);
+        });
+        assert_eq!(status.explicit_code(), Some(code));
+        assert_eq!(*data.get_mut(), 0);
+    }
+}
+
+#[test]
+#[cfg_attr(not(feature = "emulated"), ignore = "needs RTM")]
+fn try_transaction_hands_back_error() {
+    let mut data = TxCell::new(0u64);
+    loop {
+        let out: Result<(), TransactionError<u8>> =
+            rtm::try_transaction(&mut data, |data| unsafe {
+                data.set(1);
+                Err(9)
+            });
+        match out {
+            Err(TransactionError::Failed(code)) => {
+                assert_eq!(code, 9);
+                break;
+            }
+            Err(TransactionError::Aborted(_)) => continue,
+            Ok(()) => panic!("transaction committed"),
+        }
+    }
+    assert_eq!(*data.get_mut(), 0);
+}
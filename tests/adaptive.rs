@@ -0,0 +1,150 @@
+#![cfg(feature = "fault-injection")]
+
+extern crate rtm;
+
+use rtm::inject::{self, Schedule, ScheduleGuard};
+use rtm::{AbortCode, Adaptive, Commit, Fallback, TxCell};
+
+fn run(site: &Adaptive, data: &mut TxCell<u64>) -> Commit {
+    site.transaction_retry(data, |d| unsafe { d.set(d.get() + 1) }, 0, Fallback::Global)
+        .unwrap()
+}
+
+/// Calls made until one attempts the hardware path again,
+/// including that probe.
+fn until_probe(site: &Adaptive, data: &mut TxCell<u64>, guard: &ScheduleGuard) -> usize {
+    let mut calls = 0;
+    loop {
+        let attempts = guard.attempts();
+        run(site, data);
+        calls += 1;
+        if guard.attempts() > attempts {
+            return calls;
+        }
+    }
+}
+
+fn capacity_always() -> ScheduleGuard {
+    inject::install(Schedule::new().random(1.0, 0, AbortCode::Capacity))
+}
+
+#[test]
+fn capacity_streak_disables() {
+    let site = Adaptive::new();
+    let mut data = TxCell::new(0u64);
+    let _guard = capacity_always();
+    for _ in 0..2 {
+        run(&site, &mut data);
+        assert!(!site.is_disabled());
+    }
+    run(&site, &mut data);
+    assert!(site.is_disabled());
+    assert_eq!(site.capacity_aborts(), 3);
+
+    // skipped calls report the capacity abort that disabled it
+    match run(&site, &mut data) {
+        Commit::Fallback(status) => assert!(status.capacity()),
+        Commit::Hardware => panic!("hardware path was skipped"),
+    }
+    assert_eq!(site.skipped(), 1);
+    assert_eq!(*data.get_mut(), 4);
+}
+
+#[test]
+fn other_aborts_break_the_streak() {
+    let site = Adaptive::new();
+    let mut data = TxCell::new(0u64);
+    let _guard = inject::install(
+        Schedule::new()
+            .nth(1, AbortCode::Capacity)
+            .nth(2, AbortCode::Capacity)
+            .nth(3, AbortCode::Conflict)
+            .nth(4, AbortCode::Capacity)
+            .nth(5, AbortCode::Capacity),
+    );
+    for _ in 0..5 {
+        run(&site, &mut data);
+    }
+    assert!(!site.is_disabled());
+    assert_eq!(site.capacity_aborts(), 4);
+}
+
+#[test]
+fn failed_probes_double_the_skip() {
+    let site = Adaptive::new();
+    let mut data = TxCell::new(0u64);
+    let guard = capacity_always();
+    for _ in 0..3 {
+        run(&site, &mut data);
+    }
+    assert!(site.is_disabled());
+
+    // a single failed probe disables it again
+    assert_eq!(until_probe(&site, &mut data, &guard), 17);
+    assert!(site.is_disabled());
+    assert_eq!(until_probe(&site, &mut data, &guard), 33);
+    assert_eq!(until_probe(&site, &mut data, &guard), 65);
+    assert_eq!(site.skipped(), 16 + 32 + 64);
+}
+
+#[test]
+fn skip_is_capped() {
+    let site = Adaptive::with_limits(1, 40);
+    let mut data = TxCell::new(0u64);
+    let guard = capacity_always();
+    run(&site, &mut data);
+    assert!(site.is_disabled());
+    let windows: Vec<usize> = (0..4)
+        .map(|_| until_probe(&site, &mut data, &guard))
+        .collect();
+    assert_eq!(windows, vec![17, 33, 41, 41]);
+}
+
+#[test]
+fn successful_probe_reenables() {
+    let site = Adaptive::new();
+    let mut data = TxCell::new(0u64);
+    let guard = capacity_always();
+    for _ in 0..3 {
+        run(&site, &mut data);
+    }
+    assert_eq!(until_probe(&site, &mut data, &guard), 17);
+    assert_eq!(until_probe(&site, &mut data, &guard), 33);
+    drop(guard);
+
+    // the probe no longer hits a capacity abort
+    let guard = inject::install(Schedule::new());
+    assert_eq!(until_probe(&site, &mut data, &guard), 65);
+    assert!(!site.is_disabled());
+    let commit = run(&site, &mut data);
+    assert_eq!(guard.attempts(), 2);
+    if rtm::is_supported() {
+        assert_eq!(commit, Commit::Hardware);
+    }
+}
+
+/// After a commit the skip starts over from the smallest window.
+#[cfg(feature = "emulated")]
+#[test]
+fn commit_resets_the_skip() {
+    let site = Adaptive::new();
+    let mut data = TxCell::new(0u64);
+    let guard = capacity_always();
+    for _ in 0..3 {
+        run(&site, &mut data);
+    }
+    assert_eq!(until_probe(&site, &mut data, &guard), 17);
+    drop(guard);
+    let guard = inject::install(Schedule::new());
+    assert_eq!(until_probe(&site, &mut data, &guard), 33);
+    assert_eq!(run(&site, &mut data), Commit::Hardware);
+    drop(guard);
+
+    let guard = capacity_always();
+    for _ in 0..3 {
+        run(&site, &mut data);
+    }
+    assert!(site.is_disabled());
+    assert_eq!(until_probe(&site, &mut data, &guard), 17);
+    assert!(site.success_ratio() > 0.0);
+}
@@ -0,0 +1,56 @@
+extern crate rtm;
+
+use std::mem;
+
+use rtm::{cache_lines, CacheAligned, CachePadded, TxBox, TxCell, TxSlots, CACHE_LINE};
+
+#[test]
+fn lines_touched() {
+    assert_eq!(cache_lines::<()>(), 0);
+    assert_eq!(cache_lines::<u64>(), 1);
+    assert_eq!(cache_lines::<[u8; 2]>(), 2);
+    assert_eq!(cache_lines::<[u64; 8]>(), 2);
+    assert_eq!(cache_lines::<CacheAligned<[u64; 8]>>(), 1);
+    assert_eq!(cache_lines::<CacheAligned<[u64; 9]>>(), 2);
+    assert_eq!(cache_lines::<CachePadded<u8>>(), 2);
+    const LINES: usize = cache_lines::<[u32; 100]>();
+    assert_eq!(LINES, 8);
+}
+
+#[test]
+fn layout() {
+    assert_eq!(mem::align_of::<CacheAligned<u8>>(), CACHE_LINE);
+    assert_eq!(mem::size_of::<CacheAligned<u8>>(), CACHE_LINE);
+    assert_eq!(mem::size_of::<[CacheAligned<u16>; 3]>(), 3 * CACHE_LINE);
+    assert_eq!(mem::align_of::<CachePadded<u8>>(), 2 * CACHE_LINE);
+
+    let boxes: Vec<TxBox<u8>> = (0..8).map(TxBox::new).collect();
+    for (i, boxed) in boxes.iter().enumerate() {
+        assert_eq!(&**boxed as *const u8 as usize % CACHE_LINE, 0);
+        assert_eq!(**boxed as usize, i);
+    }
+    let mut aligned = CacheAligned::new(1);
+    *aligned += 1;
+    assert_eq!(aligned.into_inner(), 2);
+}
+
+#[test]
+fn slots_are_objects() {
+    let slots = TxSlots::new(4, 10u32);
+    assert_eq!(slots.len(), 4);
+    let a = &slots[0] as *const TxCell<u32> as usize;
+    let b = &slots[1] as *const TxCell<u32> as usize;
+    assert_eq!(b - a, CACHE_LINE);
+    rtm::transaction_many((&slots[0], &slots[3]), |(from, to)| unsafe {
+        from.set(from.get() - 5);
+        to.set(to.get() + 5);
+    })
+    .unwrap();
+    assert_eq!(
+        slots
+            .iter()
+            .map(|slot| unsafe { slot.get() })
+            .collect::<Vec<_>>(),
+        vec![5, 10, 10, 15]
+    );
+}
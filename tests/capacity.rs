@@ -0,0 +1,98 @@
+extern crate rtm;
+
+use rtm::{
+    CacheAligned, Capacity, CheckedError, Fallback, Fit, Footprint, TxFootprint, TxSlots, TxVec,
+};
+
+#[derive(Copy, Clone)]
+struct Account {
+    _balance: i64,
+    _history: [u64; 15],
+}
+impl TxFootprint for Account {}
+
+#[test]
+fn static_footprints() {
+    assert_eq!(0u64.footprint(), Footprint::lines(1));
+    assert_eq!(Footprint::of::<Account>(), Footprint::lines(3));
+    assert_eq!(CacheAligned::new(1u8).footprint(), Footprint::lines(1));
+    assert_eq!(TxSlots::new(10, 0u8).footprint().writes, 10 + 2);
+    assert!(TxVec::<u64>::with_capacity(80).footprint().writes >= 10);
+    assert_eq!((1u8, 2u8).footprint(), Footprint::lines(2));
+
+    const FIT: Fit = Capacity::L1.check(Footprint::of::<[Account; 100]>());
+    assert_eq!(FIT, Fit::Fits);
+    assert_eq!(Capacity::L1.check(Footprint::lines(300)), Fit::Tight);
+    assert_eq!(Capacity::L1.check(Footprint::lines(513)), Fit::Exceeds);
+    let reads = Footprint {
+        reads: 5000,
+        writes: 1,
+    };
+    assert_eq!(Capacity::L1.check(reads), Fit::Exceeds);
+}
+
+#[test]
+fn checked_transactions() {
+    let mut small = [0u64; 8];
+    let (_, fit) =
+        rtm::transaction_checked(&mut small, |d| d[0] += 1, 3, Fallback::Global).unwrap();
+    assert_eq!(fit, Fit::Fits);
+    assert_eq!(small[0], 1);
+
+    let mut big = Box::new([0u64; 8192]);
+    match rtm::transaction_checked(&mut *big, |d| d[0] += 1, 3, Fallback::Global) {
+        Err(CheckedError::Capacity(footprint)) => assert!(footprint.writes > 1024),
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(big[0], 0, "an oversized transaction must not run");
+}
+
+#[cfg(feature = "emulated")]
+#[test]
+fn recorded_traces() {
+    use rtm::TxCell;
+
+    let slots = TxSlots::new(100, 0u32);
+    let mut data = ();
+    let (_, trace) = rtm::emulated::record(|| {
+        rtm::transaction(
+            &mut data,
+            |_| unsafe {
+                for i in 0..40 {
+                    slots[i].set(slots[i].get() + 1);
+                }
+                slots[99].get();
+            },
+            Fallback::None,
+        )
+        .unwrap()
+    });
+    assert_eq!(
+        trace.footprint(),
+        Footprint {
+            reads: 41,
+            writes: 40
+        }
+    );
+    assert_eq!(Capacity::L1.check_trace(&trace), Fit::Fits);
+
+    // nine lines 4 KiB apart all land in one set of an 8 way L1
+    let lines: Vec<CacheAligned<TxCell<u8>>> = (0..64 * 9)
+        .map(|_| CacheAligned::new(TxCell::new(0)))
+        .collect();
+    let (_, trace) = rtm::emulated::record(|| {
+        rtm::transaction(
+            &mut data,
+            |_| unsafe {
+                for line in lines.iter().step_by(64) {
+                    line.set(1);
+                }
+            },
+            Fallback::None,
+        )
+        .unwrap()
+    });
+    assert_eq!(trace.footprint().writes, 9);
+    assert_eq!(Capacity::L1.check(trace.footprint()), Fit::Fits);
+    assert_eq!(Capacity::L1.check_trace(&trace), Fit::Exceeds);
+}
@@ -0,0 +1,51 @@
+extern crate rtm;
+
+use rtm::cpuid_supports_rtm;
+
+const RTM: u32 = 1 << 11;
+const RTM_ALWAYS_ABORT: u32 = 1 << 11;
+
+#[test]
+fn rtm_bit_is_required() {
+    assert!(cpuid_supports_rtm(7, RTM, 0));
+    assert!(!cpuid_supports_rtm(7, 0, 0));
+    assert!(!cpuid_supports_rtm(7, !RTM, 0));
+}
+
+#[test]
+fn always_abort_reports_unsupported() {
+    assert!(!cpuid_supports_rtm(7, RTM, RTM_ALWAYS_ABORT));
+    assert!(!cpuid_supports_rtm(0x20, !0, !0));
+}
+
+#[test]
+fn leaf_7_must_exist() {
+    assert!(!cpuid_supports_rtm(6, RTM, 0));
+    assert!(cpuid_supports_rtm(0x20, RTM, !RTM_ALWAYS_ABORT));
+}
+
+#[cfg(not(feature = "emulated"))]
+#[test]
+fn agrees_with_std_detection() {
+    if !is_x86_feature_detected!("rtm") {
+        assert!(!rtm::is_supported());
+    }
+}
+
+#[cfg(not(feature = "emulated"))]
+#[test]
+fn agrees_with_this_processor() {
+    use std::arch::x86_64::{__cpuid, __cpuid_count};
+
+    let max_leaf = __cpuid(0).eax;
+    let leaf = __cpuid_count(7, 0);
+    let expected = cpuid_supports_rtm(max_leaf, leaf.ebx, leaf.edx);
+    assert_eq!(rtm::is_supported(), expected);
+    assert_eq!(rtm::is_supported(), expected, "cached answer changed");
+}
+
+#[cfg(feature = "emulated")]
+#[test]
+fn emulated_is_always_supported() {
+    assert!(rtm::is_supported());
+}
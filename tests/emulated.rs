@@ -0,0 +1,59 @@
+#![cfg(feature = "emulated")]
+
+extern crate rtm;
+
+use std::cell::Cell;
+
+use rtm::emulated::inject;
+use rtm::{AbortStatus, Commit, Fallback, TxCell};
+
+#[test]
+fn injected_abort_is_reported() {
+    let mut data = TxCell::new(0u64);
+    let status = AbortStatus::from_raw(AbortStatus::CAPACITY);
+    inject(status);
+    let out = rtm::transaction(&mut data, |data| unsafe { data.set(1) }, Fallback::None);
+    assert_eq!(out, Err(status));
+    assert_eq!(*data.get_mut(), 0);
+}
+
+#[test]
+fn retry_flag_is_retried() {
+    let mut data = TxCell::new(0u64);
+    inject(AbortStatus::from_raw(AbortStatus::RETRY));
+    inject(AbortStatus::from_raw(
+        AbortStatus::RETRY | AbortStatus::CONFLICT,
+    ));
+    let out = rtm::transaction_retry(
+        &mut data,
+        |data| unsafe { data.set(data.get() + 1) },
+        3,
+        Fallback::None,
+    );
+    assert_eq!(out, Ok(Commit::Hardware));
+    assert_eq!(*data.get_mut(), 1);
+}
+
+#[test]
+fn abort_falls_back_after_rollback() {
+    let mut data = TxCell::new(0u64);
+    let runs = Cell::new(0);
+    let out = rtm::transaction(
+        &mut data,
+        |data| {
+            unsafe { data.set(data.get() + 1) };
+            // only the hardware attempt aborts, an abort on the
+            // fallback path would roll that back too
+            if runs.replace(runs.get() + 1) == 0 {
+                rtm::try_abort(3);
+            }
+        },
+        Fallback::Global,
+    );
+    let status = match out {
+        Ok(Commit::Fallback(status)) => status,
+        other => panic!("unexpected {:?}", other),
+    };
+    assert_eq!(status.explicit_code(), Some(3));
+    assert_eq!(*data.get_mut(), 1);
+}
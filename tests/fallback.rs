@@ -0,0 +1,131 @@
+extern crate rtm;
+
+use std::cell::UnsafeCell;
+use std::sync::Arc;
+use std::thread;
+
+use rtm::{Commit, Fallback, FallbackLock};
+
+const THREADS: u64 = 8;
+const ROUNDS: u64 = 5_000;
+
+/// A counter shared between threads, written only inside
+/// `transaction`.
+struct Shared(UnsafeCell<u64>);
+
+unsafe impl Sync for Shared {}
+
+impl Shared {
+    fn new(value: u64) -> Shared {
+        Shared(UnsafeCell::new(value))
+    }
+
+    /// Callers run inside `transaction` or own the counter.
+    unsafe fn get(&self) -> u64 {
+        *self.0.get()
+    }
+
+    /// Callers run inside `transaction` or own the counter.
+    unsafe fn set(&self, value: u64) {
+        *self.0.get() = value;
+    }
+}
+
+/// Keeps each counter on a cache line of its own.
+#[repr(align(64))]
+struct Line(Shared);
+
+/// Increments a shared counter from several threads, the total
+/// only adds up if both paths exclude one another.
+fn counter_adds_up<F>(fallback: F)
+where
+    F: Fn(&Shared) -> Fallback<'static> + Send + Sync + 'static,
+{
+    let counter = Arc::new(Shared::new(0u64));
+    let fallback = Arc::new(fallback);
+    let workers: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let (counter, fallback) = (counter.clone(), fallback.clone());
+            thread::spawn(move || {
+                let mut shared: &Shared = &counter;
+                for _ in 0..ROUNDS {
+                    let how = fallback(shared);
+                    let out = rtm::transaction(&mut shared, |c| unsafe { c.set(c.get() + 1) }, how);
+                    assert!(out.is_ok());
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    assert_eq!(unsafe { counter.get() }, THREADS * ROUNDS);
+}
+
+#[test]
+fn global_adds_up() {
+    counter_adds_up(|_| Fallback::Global);
+}
+
+#[test]
+fn lock_adds_up() {
+    static LOCK: FallbackLock = FallbackLock::new();
+    counter_adds_up(|_| Fallback::Lock(&LOCK));
+}
+
+#[test]
+fn striped_adds_up() {
+    counter_adds_up(Fallback::stripe_of);
+}
+
+#[test]
+fn stripe_of_matches_striped() {
+    let mut data = Shared::new(0u64);
+    let lock = match Fallback::stripe_of(&data) {
+        Fallback::Lock(lock) => lock,
+        _ => unreachable!(),
+    };
+    // only the fallback path holds the stripe, abort until it runs
+    let out = rtm::transaction(
+        &mut data,
+        |d| {
+            if !lock.is_locked() {
+                rtm::try_abort(1);
+            }
+            unsafe { d.set(1) };
+        },
+        Fallback::Striped,
+    );
+    match out {
+        Ok(Commit::Fallback(_)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(unsafe { data.get() }, 1);
+}
+
+#[test]
+fn striped_keeps_addresses_independent() {
+    let cells: Vec<Line> = (0..4).map(|_| Line(Shared::new(0))).collect();
+    let stripe = |cell: &Shared| match Fallback::stripe_of(cell) {
+        Fallback::Lock(lock) => lock,
+        _ => unreachable!(),
+    };
+    let a: &Shared = &cells[0].0;
+    let b: &Shared = cells[1..]
+        .iter()
+        .map(|cell| &cell.0)
+        .find(|cell| !std::ptr::eq(stripe(cell), stripe(a)))
+        .expect("neighbouring lines share a stripe");
+
+    // the fallback path of `b` must not wait on the stripe of `a`
+    let _held = stripe(a).lock();
+    let mut shared = b;
+    let out = rtm::transaction(&mut shared, |c| unsafe { c.set(7) }, Fallback::stripe_of(b));
+    match out {
+        Ok(Commit::Hardware) | Ok(Commit::Fallback(_)) => {}
+        Err(status) => panic!("unexpected {:?}", status),
+    }
+    assert_eq!(unsafe { b.get() }, 7);
+    assert!(stripe(a).is_locked());
+    assert!(!stripe(b).is_locked());
+}
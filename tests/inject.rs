@@ -0,0 +1,137 @@
+#![cfg(feature = "fault-injection")]
+
+extern crate rtm;
+
+use rtm::inject::{self, Schedule};
+use rtm::{AbortCode, AbortStatus, Commit, ElidedMutex, Fallback, TransactionError, TxCell};
+
+/// The status an attempt that is not injected ends with.
+fn uninjected() -> Result<Commit, AbortStatus> {
+    if rtm::is_supported() {
+        Ok(Commit::Hardware)
+    } else {
+        Ok(Commit::Fallback(AbortStatus::from_raw(0)))
+    }
+}
+
+fn increment(data: &mut TxCell<u64>) {
+    unsafe { data.set(data.get() + 1) };
+}
+
+#[test]
+fn nth_attempt_falls_back() {
+    let mut data = TxCell::new(0u64);
+    let guard = inject::install(Schedule::new().nth(1, AbortCode::Capacity));
+    let out = rtm::transaction(&mut data, increment, Fallback::Global);
+    assert_eq!(out, Ok(Commit::Fallback(AbortCode::Capacity.into())));
+    assert_eq!(guard.attempts(), 1);
+
+    // the rule only matches the first attempt
+    let out = rtm::transaction(&mut data, increment, Fallback::Global);
+    assert_eq!(out, uninjected());
+    assert_eq!(guard.attempts(), 2);
+    assert_eq!(*data.get_mut(), 2);
+}
+
+#[test]
+fn injected_retries_follow_the_policy() {
+    let mut data = TxCell::new(0u64);
+    let schedule = (1..=2).fold(Schedule::new(), |s, n| s.nth(n, AbortCode::Retry));
+    let guard = inject::install(schedule);
+    let out = rtm::transaction_retry(&mut data, increment, 5, Fallback::Global);
+    assert_eq!(out, uninjected());
+    assert_eq!(guard.attempts(), 3);
+    drop(guard);
+
+    let schedule = (1..=5).fold(Schedule::new(), |s, n| s.nth(n, AbortCode::Retry));
+    let guard = inject::install(schedule);
+    let out = rtm::transaction_retry(&mut data, increment, 2, Fallback::Global);
+    assert_eq!(out, Ok(Commit::Fallback(AbortCode::Retry.into())));
+    assert_eq!(guard.attempts(), 3);
+    assert_eq!(*data.get_mut(), 2);
+}
+
+#[test]
+fn first_matching_rule_wins() {
+    let mut data = TxCell::new(0u64);
+    let _guard = inject::install(
+        Schedule::new()
+            .nth(1, AbortCode::Capacity)
+            .nth(1, AbortCode::Conflict),
+    );
+    let status = rtm::transaction(&mut data, increment, Fallback::None).unwrap_err();
+    assert!(status.capacity());
+    assert!(!status.conflict());
+    assert_eq!(*data.get_mut(), 0);
+}
+
+/// Which of `runs` transactions failed with a conflict.
+fn conflicts(probability: f64, seed: u64, runs: usize) -> Vec<bool> {
+    let mut data = TxCell::new(0u64);
+    let _guard = inject::install(Schedule::new().random(probability, seed, AbortCode::Conflict));
+    (0..runs)
+        .map(
+            |_| match rtm::transaction(&mut data, increment, Fallback::None) {
+                Err(status) => status.conflict(),
+                Ok(_) => false,
+            },
+        )
+        .collect()
+}
+
+#[test]
+fn random_rules_replay_by_seed() {
+    let first = conflicts(0.25, 42, 400);
+    assert_eq!(first, conflicts(0.25, 42, 400));
+    assert_ne!(first, conflicts(0.25, 43, 400));
+    let hits = first.iter().filter(|&&hit| hit).count();
+    assert!((50..150).contains(&hits), "{} of 400", hits);
+}
+
+#[test]
+fn random_rule_extremes() {
+    assert!(conflicts(1.0, 1, 50).iter().all(|&hit| hit));
+    assert!(conflicts(0.0, 1, 50).iter().all(|&hit| !hit));
+}
+
+#[test]
+fn guard_restores_previous_schedule() {
+    let mut data = TxCell::new(0u64);
+    let outer = inject::install(Schedule::new().nth(2, AbortCode::Conflict));
+    let _ = rtm::transaction(&mut data, increment, Fallback::Global);
+    {
+        let inner = inject::install(Schedule::new());
+        let _ = rtm::transaction(&mut data, increment, Fallback::Global);
+        assert_eq!(inner.attempts(), 1);
+    }
+    let out = rtm::transaction(&mut data, increment, Fallback::Global);
+    assert_eq!(out, Ok(Commit::Fallback(AbortCode::Conflict.into())));
+    assert_eq!(outer.attempts(), 2);
+    assert_eq!(*data.get_mut(), 3);
+}
+
+#[test]
+fn elided_mutex_takes_the_lock() {
+    let mutex = ElidedMutex::new(0u32);
+    let _guard = inject::install(Schedule::new().nth(1, AbortCode::Capacity));
+    {
+        let mut guard = mutex.lock();
+        assert!(!guard.is_elided());
+        assert!(mutex.is_locked());
+        *guard += 1;
+    }
+    assert!(!mutex.is_locked());
+    assert_eq!(mutex.into_inner(), 1);
+}
+
+#[test]
+fn explicit_code_reaches_try_transaction() {
+    let mut data = TxCell::new(0u64);
+    let _guard = inject::install(Schedule::new().nth(1, AbortCode::from_code(42)));
+    let out: Result<(), TransactionError<u8>> = rtm::try_transaction(&mut data, |d| {
+        increment(d);
+        Ok(())
+    });
+    assert_eq!(out, Err(TransactionError::Failed(42)));
+    assert_eq!(*data.get_mut(), 0);
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "std")]
+
+extern crate rtm;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use rtm::TxHashMap;
+
+#[test]
+fn insert_get_remove() {
+    let map = TxHashMap::with_capacity(64);
+    for key in 0..64u64 {
+        assert_eq!(map.insert(key, key * 2), Ok(None));
+    }
+    assert_eq!(map.insert(3, 0), Ok(Some(6)));
+    assert_eq!(map.get(&3), Some(0));
+    assert_eq!(map.get(&64), None);
+    for key in 0..64u64 {
+        let value = if key == 3 { 0 } else { key * 2 };
+        assert_eq!(map.remove(&key), Some(value));
+        assert!(!map.contains_key(&key));
+    }
+    assert_eq!(map.remove(&0), None);
+    // tombstones are reused
+    for _ in 0..10 {
+        for key in 0..64u64 {
+            assert_eq!(map.insert(key, key), Ok(None));
+        }
+        for key in 0..64u64 {
+            assert_eq!(map.remove(&key), Some(key));
+        }
+    }
+}
+
+#[test]
+fn full_table() {
+    let map = TxHashMap::with_capacity(1);
+    let mut stored = Vec::new();
+    for key in 0..map.capacity() as u32 + 1 {
+        if map.insert(key, key).is_ok() {
+            stored.push(key);
+        }
+    }
+    assert!(stored.len() <= map.capacity());
+    assert_eq!(map.insert(u32::MAX, 0), Err((u32::MAX, 0)));
+    for key in stored {
+        assert_eq!(map.get(&key), Some(key));
+    }
+}
+
+#[test]
+fn insert_many_is_all_or_nothing() {
+    let map = TxHashMap::with_capacity(1);
+    let entries: Vec<(u32, u32)> = (0..map.capacity() as u32 + 1).map(|k| (k, k)).collect();
+    assert!(!map.insert_many(&entries));
+    for &(key, _) in &entries {
+        assert_eq!(map.get(&key), None);
+    }
+    assert!(map.insert_many(&[(1, 1), (2, 2), (1, 3)]));
+    assert_eq!(map.get(&1), Some(3));
+    assert_eq!(map.get(&2), Some(2));
+}
+
+#[test]
+fn transfer() {
+    let map = TxHashMap::with_capacity(8);
+    map.insert(1u8, 'a').unwrap();
+    map.insert(2u8, 'b').unwrap();
+    assert!(map.transfer(&1, 2));
+    assert_eq!(map.get(&1), None);
+    assert_eq!(map.get(&2), Some('a'));
+    assert!(!map.transfer(&1, 3));
+    assert_eq!(map.get(&3), None);
+}
+
+/// A single token is moved around a ring of keys, a torn
+/// transfer would lose or duplicate it.
+#[test]
+fn concurrent_transfers() {
+    const RING: usize = 8;
+    let map = Arc::new(TxHashMap::with_capacity(RING));
+    map.insert(0usize, ()).unwrap();
+    let moves = Arc::new(AtomicUsize::new(0));
+    let threads: Vec<_> = (0..4)
+        .map(|t| {
+            let map = map.clone();
+            let moves = moves.clone();
+            thread::spawn(move || {
+                for i in 0..20_000 {
+                    let from = (i * (t + 1)) % RING;
+                    if map.transfer(&from, (from + 1) % RING) {
+                        moves.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let holders: Vec<usize> = (0..RING).filter(|key| map.contains_key(key)).collect();
+    assert_eq!(holders, vec![moves.load(Ordering::Relaxed) % RING]);
+}
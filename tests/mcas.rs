@@ -0,0 +1,148 @@
+#![cfg(feature = "std")]
+
+extern crate rtm;
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+use std::thread;
+
+use rtm::{mcas, mcas_read, McasError};
+
+/// Values keep the two low bits clear, `mcas` reserves them.
+const ONE: usize = 4;
+
+#[test]
+fn updates_all_or_nothing() {
+    let a = AtomicUsize::new(0);
+    let b = AtomicUsize::new(ONE);
+    assert_eq!(mcas(&[(&a, 0, 8), (&b, ONE, 12)]), Ok(()));
+    assert_eq!((mcas_read(&a), mcas_read(&b)), (8, 12));
+
+    let out = mcas(&[(&a, 8, 16), (&b, ONE, 20)]);
+    assert_eq!(out, Err(McasError::Mismatch { index: 1 }));
+    assert_eq!((mcas_read(&a), mcas_read(&b)), (8, 12));
+}
+
+#[test]
+fn rejects_bad_input() {
+    let a = AtomicUsize::new(0);
+    let b = AtomicUsize::new(0);
+    assert_eq!(
+        mcas(&[(&a, 0, 4), (&b, 0, 1)]),
+        Err(McasError::Reserved { index: 1 })
+    );
+    assert_eq!(
+        mcas(&[(&a, 0, 4), (&b, 0, 4), (&a, 0, 8)]),
+        Err(McasError::Duplicate { index: 2 })
+    );
+    assert_eq!(mcas_read(&a), 0);
+    assert_eq!(mcas(&[]), Ok(()));
+}
+
+/// Reads every word, then confirms with an `mcas` that writes the
+/// same values back. The snapshot is only returned if it held at
+/// a single point in time.
+fn snapshot(words: &[AtomicUsize]) -> Vec<usize> {
+    loop {
+        let values: Vec<usize> = words.iter().map(mcas_read).collect();
+        let check: Vec<_> = words.iter().zip(&values).map(|(w, &v)| (w, v, v)).collect();
+        if mcas(&check).is_ok() {
+            return values;
+        }
+    }
+}
+
+/// Every update increments all words together, so any
+/// linearizable snapshot sees them equal.
+#[test]
+fn concurrent_increments_are_atomic() {
+    const WORDS: usize = 4;
+    const THREADS: usize = 4;
+    const ROUNDS: usize = 5_000;
+    let words: Arc<Vec<AtomicUsize>> = Arc::new((0..WORDS).map(|_| AtomicUsize::new(0)).collect());
+    let mut threads = Vec::new();
+    for _ in 0..THREADS {
+        let words = words.clone();
+        threads.push(thread::spawn(move || {
+            for _ in 0..ROUNDS {
+                loop {
+                    let values: Vec<usize> = words.iter().map(mcas_read).collect();
+                    let update: Vec<_> = words
+                        .iter()
+                        .zip(&values)
+                        .map(|(w, &v)| (w, v, v + ONE))
+                        .collect();
+                    if mcas(&update).is_ok() {
+                        break;
+                    }
+                }
+            }
+        }));
+    }
+    for _ in 0..2 {
+        let words = words.clone();
+        threads.push(thread::spawn(move || {
+            for _ in 0..2_000 {
+                let values = snapshot(&words);
+                assert!(values.iter().all(|&v| v == values[0]), "torn: {:?}", values);
+            }
+        }));
+    }
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    for word in words.iter() {
+        assert_eq!(mcas_read(word), THREADS * ROUNDS * ONE);
+    }
+}
+
+/// Transfers between overlapping pairs keep the total constant
+/// in every snapshot.
+#[test]
+fn concurrent_transfers_keep_total() {
+    const ACCOUNTS: usize = 8;
+    const BALANCE: usize = 100 * ONE;
+    let accounts: Arc<Vec<AtomicUsize>> =
+        Arc::new((0..ACCOUNTS).map(|_| AtomicUsize::new(BALANCE)).collect());
+    let mut threads = Vec::new();
+    for seed in 0..4u64 {
+        let accounts = accounts.clone();
+        threads.push(thread::spawn(move || {
+            let mut state = seed * 7919 + 1;
+            for _ in 0..10_000 {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let from = state as usize % ACCOUNTS;
+                let to = (state >> 32) as usize % ACCOUNTS;
+                if from == to {
+                    continue;
+                }
+                loop {
+                    let (a, b) = (mcas_read(&accounts[from]), mcas_read(&accounts[to]));
+                    if a == 0 {
+                        break;
+                    }
+                    let update = [(&accounts[from], a, a - ONE), (&accounts[to], b, b + ONE)];
+                    if mcas(&update).is_ok() {
+                        break;
+                    }
+                }
+            }
+        }));
+    }
+    {
+        let accounts = accounts.clone();
+        threads.push(thread::spawn(move || {
+            for _ in 0..2_000 {
+                let total: usize = snapshot(&accounts).iter().sum();
+                assert_eq!(total, BALANCE * ACCOUNTS);
+            }
+        }));
+    }
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let total: usize = accounts.iter().map(mcas_read).sum();
+    assert_eq!(total, BALANCE * ACCOUNTS);
+}
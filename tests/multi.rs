@@ -0,0 +1,76 @@
+extern crate rtm;
+
+use std::sync::Arc;
+use std::thread;
+
+use rtm::{Commit, TxCell, TxVec};
+
+#[test]
+fn updates_every_object() {
+    let a = Arc::new(TxCell::new(10i64));
+    let b = Arc::new(TxCell::new(0i64));
+    let log = TxVec::with_capacity(4);
+    let commit = rtm::transaction_many((&*a, &*b, &log), |(a, b, log)| unsafe {
+        a.set(a.get() - 3);
+        b.set(b.get() + 3);
+        let _ = log.push(3u8);
+    })
+    .unwrap();
+    assert!(matches!(commit, Commit::Hardware | Commit::Fallback(_)));
+    unsafe {
+        assert_eq!((a.get(), b.get()), (7, 3));
+        assert_eq!(log.iter().collect::<Vec<_>>(), vec![3]);
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn abort_rolls_back_every_object() {
+    let a = TxCell::new(1u32);
+    let b = TxCell::new(2u32);
+    let status = rtm::transaction_many([&a, &b], |[a, b]| unsafe {
+        a.set(0);
+        b.set(0);
+        rtm::abort(9);
+    })
+    .unwrap_err();
+    assert_eq!(status.explicit_code(), Some(9));
+    assert_eq!(unsafe { (a.get(), b.get()) }, (1, 2));
+}
+
+/// Money moves between accounts owned by separate `Arc`s, the
+/// total never changes.
+#[test]
+fn concurrent_transfers() {
+    const ACCOUNTS: usize = 6;
+    let accounts: Vec<Arc<TxCell<i64>>> =
+        (0..ACCOUNTS).map(|_| Arc::new(TxCell::new(100))).collect();
+    let threads: Vec<_> = (0..4)
+        .map(|t| {
+            let accounts = accounts.clone();
+            thread::spawn(move || {
+                for i in 0..20_000 {
+                    let from = &accounts[(i + t) % ACCOUNTS];
+                    let to = &accounts[(i * 7 + t + 1) % ACCOUNTS];
+                    rtm::transaction_many_retry(
+                        (&**from, &**to),
+                        |(from, to)| unsafe {
+                            from.set(from.get() - 1);
+                            to.set(to.get() + 1);
+                        },
+                        3,
+                    )
+                    .unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let total: i64 = accounts
+        .iter()
+        .map(|account| unsafe { account.get() })
+        .sum();
+    assert_eq!(total, 100 * ACCOUNTS as i64);
+}
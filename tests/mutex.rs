@@ -0,0 +1,92 @@
+extern crate rtm;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+
+use rtm::ElidedMutex;
+
+#[test]
+fn lock_gives_access() {
+    let mutex = ElidedMutex::new(vec![1, 2]);
+    {
+        let mut guard = mutex.lock();
+        guard.push(3);
+        if !rtm::is_supported() {
+            // without RTM every lock takes the fallback lock
+            assert!(!guard.is_elided());
+            assert!(mutex.is_locked());
+        }
+    }
+    assert!(!mutex.is_locked());
+    assert_eq!(mutex.into_inner(), vec![1, 2, 3]);
+}
+
+/// The holder makes a system call, which aborts an elided
+/// section, so past it the fallback lock is held. The emulator
+/// cannot abort on system calls.
+#[cfg(not(feature = "emulated"))]
+#[test]
+fn try_lock_contends_with_fallback_holder() {
+    use std::sync::Barrier;
+
+    let mutex = Arc::new(ElidedMutex::new(0));
+    let held = Arc::new(Barrier::new(2));
+    let checked = Arc::new(Barrier::new(2));
+    let holder = {
+        let (mutex, held, checked) = (mutex.clone(), held.clone(), checked.clone());
+        thread::spawn(move || {
+            let mut guard = mutex.lock();
+            thread::yield_now();
+            assert!(!guard.is_elided());
+            *guard = 1;
+            held.wait();
+            checked.wait();
+        })
+    };
+    held.wait();
+    assert!(mutex.is_locked());
+    assert!(mutex.try_lock().is_none());
+    checked.wait();
+    holder.join().unwrap();
+    assert!(!mutex.is_locked());
+    assert_eq!(*mutex.try_lock().unwrap(), 1);
+    assert_eq!(*mutex.lock(), 1);
+}
+
+#[test]
+fn guard_releases_on_drop_and_panic() {
+    let mutex = ElidedMutex::new(0);
+    drop(mutex.lock());
+    assert!(!mutex.is_locked());
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _guard = mutex.lock();
+        panic!("inside the critical section");
+    }));
+    assert!(result.is_err());
+    assert!(!mutex.is_locked());
+    *mutex.lock() += 1;
+    assert_eq!(*mutex.try_lock().unwrap(), 1);
+}
+
+#[test]
+fn counter_adds_up() {
+    const THREADS: usize = 8;
+    const INCREMENTS: usize = 10_000;
+    let mutex = Arc::new(ElidedMutex::new(0usize));
+    let threads: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let mutex = mutex.clone();
+            thread::spawn(move || {
+                for _ in 0..INCREMENTS {
+                    *mutex.lock() += 1;
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(*mutex.lock(), THREADS * INCREMENTS);
+}
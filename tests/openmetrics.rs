@@ -0,0 +1,172 @@
+#![cfg(feature = "stats")]
+
+extern crate rtm;
+
+use std::collections::{BTreeMap, HashSet};
+
+use rtm::{AbortStatus, ElidedMutex, Fallback, TxCell};
+
+/// A sample, its labels sorted by name.
+type Key = (String, Vec<(String, String)>);
+
+/// Parses an OpenMetrics text payload made up of counters,
+/// rejecting anything this crate should never produce.
+fn parse(text: &str) -> BTreeMap<Key, u64> {
+    let mut families = HashSet::new();
+    let mut samples = BTreeMap::new();
+    let mut lines = text.lines();
+    assert!(text.ends_with("# EOF\n"), "missing # EOF");
+    for line in lines.by_ref() {
+        if line == "# EOF" {
+            break;
+        }
+        if let Some(meta) = line.strip_prefix("# ") {
+            let mut parts = meta.splitn(3, ' ');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("TYPE"), Some(name), Some("counter")) => {
+                    assert!(families.insert(name.to_string()), "{} declared twice", name);
+                }
+                (Some("HELP"), Some(name), Some(_)) => {
+                    assert!(families.contains(name), "help before type: {}", line);
+                }
+                _ => panic!("bad metadata: {}", line),
+            }
+            continue;
+        }
+        let (key, value) = parse_sample(line);
+        let family = key
+            .0
+            .strip_suffix("_total")
+            .expect("counter without _total");
+        assert!(families.contains(family), "undeclared family: {}", line);
+        assert!(samples.insert(key, value).is_none(), "duplicate: {}", line);
+    }
+    assert_eq!(lines.next(), None, "data after # EOF");
+    samples
+}
+
+fn parse_sample(line: &str) -> (Key, u64) {
+    let open = line.find('{').expect("sample without labels");
+    let name = line[..open].to_string();
+    let mut labels = Vec::new();
+    let mut rest = &line[open + 1..];
+    loop {
+        let eq = rest.find("=\"").expect("label without value");
+        let label = rest[..eq].to_string();
+        let mut value = String::new();
+        let mut chars = rest[eq + 2..].char_indices();
+        let end = loop {
+            match chars.next().expect("unterminated label value") {
+                (_, '\\') => match chars.next().expect("dangling escape").1 {
+                    '\\' => value.push('\\'),
+                    '"' => value.push('"'),
+                    'n' => value.push('\n'),
+                    c => panic!("bad escape \\{}", c),
+                },
+                (i, '"') => break eq + 2 + i + 1,
+                (_, c) => value.push(c),
+            }
+        };
+        labels.push((label, value));
+        rest = &rest[end..];
+        match rest.as_bytes()[0] {
+            b',' => rest = &rest[1..],
+            b'}' => break,
+            _ => panic!("bad label set: {}", line),
+        }
+    }
+    let value = rest[1..].strip_prefix(' ').expect("missing value");
+    labels.sort();
+    (
+        (name, labels),
+        value.parse().expect("value is not a counter"),
+    )
+}
+
+fn key(name: &str, labels: &[(&str, &str)]) -> Key {
+    let mut labels: Vec<(String, String)> = labels
+        .iter()
+        .map(|&(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    labels.sort();
+    (name.to_string(), labels)
+}
+
+fn get(samples: &BTreeMap<Key, u64>, name: &str, labels: &[(&str, &str)]) -> u64 {
+    *samples
+        .get(&key(name, labels))
+        .unwrap_or_else(|| panic!("missing {} {:?}", name, labels))
+}
+
+#[test]
+fn output_parses_back() {
+    let mutex = ElidedMutex::new(0u64);
+    for _ in 0..10 {
+        *mutex.lock() += 1;
+    }
+    let mut data = TxCell::new(0u64);
+    for _ in 0..10 {
+        rtm::transaction_retry(
+            &mut data,
+            |data| unsafe { data.set(data.get() + 1) },
+            3,
+            Fallback::Global,
+        )
+        .unwrap();
+    }
+    let _ = rtm::try_transaction(&mut data, |_| Err::<(), u8>(7));
+
+    let snapshot = rtm::stats::snapshot();
+    let mut text = String::new();
+    snapshot.write_openmetrics(&mut text).unwrap();
+    let samples = parse(&text);
+
+    let mut sites = 0;
+    for (location, counts) in snapshot.sites() {
+        sites += 1;
+        let site = location.to_string();
+        let site = [("site", site.as_str())];
+        assert_eq!(
+            get(&samples, "rtm_attempts_total", &site),
+            counts.attempts()
+        );
+        assert_eq!(get(&samples, "rtm_commits_total", &site), counts.commits());
+        assert_eq!(
+            get(&samples, "rtm_fallbacks_total", &site),
+            counts.fallbacks()
+        );
+        assert_eq!(get(&samples, "rtm_aborts_total", &site), counts.aborts());
+        for &(reason, flag) in &[
+            ("explicit", AbortStatus::EXPLICIT),
+            ("retry", AbortStatus::RETRY),
+            ("conflict", AbortStatus::CONFLICT),
+            ("capacity", AbortStatus::CAPACITY),
+            ("debug", AbortStatus::DEBUG),
+            ("nested", AbortStatus::NESTED),
+        ] {
+            let labels = [site[0], ("reason", reason)];
+            assert_eq!(
+                get(&samples, "rtm_abort_reasons_total", &labels),
+                counts.aborts_with(flag)
+            );
+        }
+        for code in 0..=255u8 {
+            let code_label = code.to_string();
+            let labels = [site[0], ("code", code_label.as_str())];
+            let key = key("rtm_explicit_aborts_total", &labels);
+            assert_eq!(
+                samples.get(&key).cloned().unwrap_or(0),
+                counts.explicit_code(code)
+            );
+        }
+    }
+    let total = snapshot.total();
+    assert_eq!(total.commits() + total.fallbacks(), 20);
+    // try_transaction never falls back, it only shows up once
+    // a hardware attempt has been made
+    assert!(sites >= 2);
+    if rtm::is_supported() {
+        assert_eq!(sites, 3);
+        assert_eq!(snapshot.total().explicit_code(7), 1);
+    }
+}
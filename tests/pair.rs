@@ -0,0 +1,49 @@
+extern crate rtm;
+
+use std::sync::Arc;
+use std::thread;
+
+use rtm::AtomicPair;
+
+#[test]
+fn operations() {
+    let pair = AtomicPair::new(1u64, -1i32);
+    assert_eq!(pair.load(), (1, -1));
+    assert_eq!(pair.swap(2, -2), (1, -1));
+    assert_eq!(pair.compare_exchange((2, -1), (3, -3)), Err((2, -2)));
+    assert_eq!(pair.compare_exchange((2, -2), (3, -3)), Ok((2, -2)));
+    pair.store(4, 4);
+    assert_eq!(pair.into_inner(), (4, 4));
+}
+
+/// A pointer and an ABA counter, every update bumps the counter
+/// so both halves always move together.
+#[test]
+fn concurrent_updates_are_atomic() {
+    let slots = Arc::new([0u8; 4]);
+    let pair = Arc::new(AtomicPair::new(slots.as_ptr(), 0usize));
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let pair = pair.clone();
+            let slots = slots.clone();
+            thread::spawn(move || {
+                for _ in 0..20_000 {
+                    let mut current = pair.load();
+                    loop {
+                        let (ptr, count) = current;
+                        assert_eq!(ptr, slots[(count % 4)..].as_ptr(), "torn pair");
+                        let next = (slots[((count + 1) % 4)..].as_ptr(), count + 1);
+                        match pair.compare_exchange(current, next) {
+                            Ok(_) => break,
+                            Err(found) => current = found,
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(pair.load().1, 80_000);
+}
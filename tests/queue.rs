@@ -0,0 +1,101 @@
+extern crate rtm;
+
+use std::sync::Arc;
+use std::thread;
+
+use rtm::TxQueue;
+
+#[test]
+fn fifo() {
+    let queue = TxQueue::with_capacity(3);
+    assert!(queue.is_empty());
+    assert_eq!(queue.push(1), Ok(()));
+    assert_eq!(queue.push(2), Ok(()));
+    assert_eq!(queue.pop(), Some(1));
+    assert_eq!(queue.push(3), Ok(()));
+    assert_eq!(queue.push(4), Ok(()));
+    assert_eq!(queue.push(5), Err(5));
+    assert_eq!(queue.len(), 3);
+    assert_eq!(queue.pop(), Some(2));
+    assert_eq!(queue.pop(), Some(3));
+    assert_eq!(queue.pop(), Some(4));
+    assert_eq!(queue.pop(), None);
+}
+
+#[test]
+fn batches() {
+    let queue = TxQueue::with_capacity(4);
+    assert!(queue.push_many(&[1, 2, 3]));
+    assert!(!queue.push_many(&[4, 5]));
+    assert_eq!(queue.len(), 3);
+    let mut out = [0; 2];
+    assert_eq!(queue.pop_many(&mut out), 2);
+    assert_eq!(out, [1, 2]);
+    assert!(queue.push_many(&[4, 5, 6]));
+    let mut out = [0; 8];
+    assert_eq!(queue.pop_many(&mut out), 4);
+    assert_eq!(out[..4], [3, 4, 5, 6]);
+    assert_eq!(queue.pop_many(&mut out), 0);
+}
+
+/// Producers push batches of increasing values, consumers pop
+/// batches. Every value arrives once and, per producer, in order.
+#[test]
+fn producers_and_consumers() {
+    const PRODUCERS: usize = 3;
+    const BATCHES: usize = 5_000;
+    let queue = Arc::new(TxQueue::with_capacity(64));
+    let producers: Vec<_> = (0..PRODUCERS)
+        .map(|p| {
+            let queue = queue.clone();
+            thread::spawn(move || {
+                for batch in 0..BATCHES {
+                    let values = [(p, batch * 2), (p, batch * 2 + 1)];
+                    while !queue.push_many(&values) {
+                        thread::yield_now();
+                    }
+                }
+            })
+        })
+        .collect();
+    let consumers: Vec<_> = (0..2)
+        .map(|_| {
+            let queue = queue.clone();
+            thread::spawn(move || {
+                let mut seen = vec![Vec::new(); PRODUCERS];
+                let mut out = [(0, 0); 5];
+                let mut idle = 0;
+                while idle < 1_000 {
+                    let n = queue.pop_many(&mut out);
+                    if n == 0 {
+                        idle += 1;
+                        thread::yield_now();
+                        continue;
+                    }
+                    idle = 0;
+                    for &(p, value) in &out[..n] {
+                        seen[p].push(value);
+                    }
+                }
+                seen
+            })
+        })
+        .collect();
+    for producer in producers {
+        producer.join().unwrap();
+    }
+    let mut all = vec![Vec::new(); PRODUCERS];
+    for consumer in consumers {
+        for (p, values) in consumer.join().unwrap().into_iter().enumerate() {
+            assert!(values.windows(2).all(|w| w[0] < w[1]), "out of order");
+            all[p].extend(values);
+        }
+    }
+    while let Some((p, value)) = queue.pop() {
+        all[p].push(value);
+    }
+    for mut values in all {
+        values.sort_unstable();
+        assert_eq!(values, (0..BATCHES * 2).collect::<Vec<_>>());
+    }
+}
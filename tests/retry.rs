@@ -0,0 +1,131 @@
+extern crate rtm;
+
+use rtm::{
+    AbortCode, AbortStatus, Backoff, FallbackLock, Fixed, Jitter, RetryConflicts, RetryPolicy,
+    UntilUnlocked, LOCK_BUSY,
+};
+
+fn capacity() -> AbortStatus {
+    AbortCode::Capacity.into()
+}
+
+fn explicit(code: u8) -> AbortStatus {
+    AbortCode::from_code(code).into()
+}
+
+fn conflict() -> AbortStatus {
+    AbortCode::Conflict.into()
+}
+
+fn retry() -> AbortStatus {
+    AbortCode::Retry.into()
+}
+
+/// The attempts after which `policy` retries `status`.
+fn retried<P: RetryPolicy>(mut policy: P, status: AbortStatus) -> Vec<usize> {
+    (1..=10)
+        .filter(|&attempt| policy.retry(attempt, status))
+        .collect()
+}
+
+#[test]
+fn fixed_only_retries_retry_and_lock_busy() {
+    assert_eq!(retried(Fixed(3), retry()), vec![1, 2, 3]);
+    assert_eq!(retried(Fixed(3), explicit(LOCK_BUSY)), vec![1, 2, 3]);
+    assert_eq!(retried(Fixed(3), conflict()), Vec::<usize>::new());
+    assert_eq!(retried(Fixed(3), capacity()), Vec::<usize>::new());
+    assert_eq!(retried(Fixed(3), explicit(1)), Vec::<usize>::new());
+    assert_eq!(retried(Fixed(0), retry()), Vec::<usize>::new());
+    assert_eq!(retried(2usize, retry()), vec![1, 2]);
+    assert_eq!(retried(Option::None::<usize>, retry()), Vec::<usize>::new());
+}
+
+#[test]
+fn transient_policies_skip_capacity_and_explicit() {
+    for status in [
+        retry(),
+        conflict(),
+        AbortStatus::from_raw(0),
+        explicit(LOCK_BUSY),
+    ] {
+        assert_eq!(
+            retried(RetryConflicts { retries: 4 }, status),
+            vec![1, 2, 3, 4]
+        );
+        let backoff = Backoff {
+            retries: 4,
+            min: 1,
+            max: 8,
+        };
+        assert_eq!(retried(backoff, status), vec![1, 2, 3, 4]);
+        assert_eq!(retried(Jitter::new(4, 1, 8, 7), status), vec![1, 2, 3, 4]);
+        let lock = FallbackLock::new();
+        let until = UntilUnlocked {
+            lock: &lock,
+            retries: 4,
+        };
+        assert_eq!(retried(until, status), vec![1, 2, 3, 4]);
+    }
+    for status in [
+        capacity(),
+        explicit(3),
+        AbortStatus::from_raw(AbortStatus::CAPACITY | AbortStatus::RETRY),
+    ] {
+        assert_eq!(
+            retried(RetryConflicts { retries: 4 }, status),
+            Vec::<usize>::new()
+        );
+        assert_eq!(
+            retried(Jitter::new(4, 1, 8, 7), status),
+            Vec::<usize>::new()
+        );
+    }
+}
+
+#[test]
+fn backoff_doubles_up_to_max() {
+    let backoff = Backoff {
+        retries: 64,
+        min: 3,
+        max: 40,
+    };
+    let pauses: Vec<u32> = (1..=6).map(|attempt| backoff.pauses(attempt)).collect();
+    assert_eq!(pauses, vec![3, 6, 12, 24, 40, 40]);
+    assert_eq!(backoff.pauses(64), 40);
+}
+
+#[test]
+fn zero_min_still_backs_off() {
+    let backoff = Backoff {
+        retries: 8,
+        min: 0,
+        max: 1000,
+    };
+    assert_eq!(backoff.pauses(1), 1);
+    assert_eq!(backoff.pauses(4), 8);
+
+    let mut jitter = Jitter::new(8, 0, 1000, 3);
+    assert!((0..100).any(|_| jitter.pauses(8) > 0));
+}
+
+#[test]
+fn jitter_stays_below_backoff() {
+    let backoff = Backoff {
+        retries: 64,
+        min: 2,
+        max: 100,
+    };
+    let mut jitter = Jitter::new(64, 2, 100, 11);
+    for attempt in 1..=64 {
+        assert!(jitter.pauses(attempt) <= backoff.pauses(attempt));
+    }
+}
+
+#[test]
+fn jitter_replays_by_seed() {
+    let mut a = Jitter::new(16, 4, 1 << 20, 99);
+    let mut b = Jitter::new(16, 4, 1 << 20, 99);
+    let first: Vec<u32> = (1..=16).map(|attempt| a.pauses(attempt)).collect();
+    let second: Vec<u32> = (1..=16).map(|attempt| b.pauses(attempt)).collect();
+    assert_eq!(first, second);
+}
@@ -0,0 +1,151 @@
+extern crate rtm;
+
+use std::sync::Arc;
+use std::thread;
+
+use rtm::ElidedRwLock;
+
+#[test]
+fn read_and_write() {
+    let lock = ElidedRwLock::new(1);
+    {
+        let a = lock.read();
+        let b = lock.read();
+        assert_eq!(*a + *b, 2);
+        if !rtm::is_supported() {
+            assert!(!a.is_elided());
+        }
+    }
+    *lock.write() += 1;
+    assert_eq!(*lock.try_read().unwrap(), 2);
+    *lock.try_write().unwrap() += 1;
+    assert_eq!(lock.into_inner(), 3);
+}
+
+/// Holders make a system call, which aborts an elided section,
+/// so past it the fallback lock is held. The emulator cannot
+/// abort on system calls.
+#[cfg(not(feature = "emulated"))]
+mod fallback {
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    use rtm::ElidedRwLock;
+
+    /// Runs `hold` on another thread, then `check` while it holds
+    /// its guards.
+    fn while_held<H, C>(lock: &Arc<ElidedRwLock<u32>>, hold: H, check: C)
+    where
+        H: FnOnce(&ElidedRwLock<u32>, &Barrier, &Barrier) + Send + 'static,
+        C: FnOnce(&ElidedRwLock<u32>),
+    {
+        let held = Arc::new(Barrier::new(2));
+        let checked = Arc::new(Barrier::new(2));
+        let holder = {
+            let (lock, held, checked) = (lock.clone(), held.clone(), checked.clone());
+            thread::spawn(move || hold(&lock, &held, &checked))
+        };
+        held.wait();
+        check(lock);
+        checked.wait();
+        holder.join().unwrap();
+    }
+
+    #[test]
+    fn writer_excludes_everyone() {
+        let lock = Arc::new(ElidedRwLock::new(0));
+        while_held(
+            &lock,
+            |lock, held, checked| {
+                let mut guard = lock.write();
+                thread::yield_now();
+                assert!(!guard.is_elided());
+                *guard = 1;
+                held.wait();
+                checked.wait();
+            },
+            |lock| {
+                assert!(lock.try_read().is_none());
+                assert!(lock.try_write().is_none());
+            },
+        );
+        assert_eq!(*lock.try_write().unwrap(), 1);
+    }
+
+    #[test]
+    fn readers_exclude_writers() {
+        let lock = Arc::new(ElidedRwLock::new(0));
+        while_held(
+            &lock,
+            |lock, held, checked| {
+                let guard = lock.read();
+                thread::yield_now();
+                assert!(!guard.is_elided());
+                held.wait();
+                checked.wait();
+            },
+            |lock| {
+                assert!(lock.try_write().is_none());
+                assert_eq!(*lock.try_read().unwrap(), 0);
+            },
+        );
+        *lock.try_write().unwrap() = 2;
+        assert_eq!(*lock.read(), 2);
+    }
+
+    #[test]
+    fn waiting_writer_holds_off_readers() {
+        let lock = Arc::new(ElidedRwLock::new(0));
+        let reader = lock.read();
+        thread::yield_now();
+        assert!(!reader.is_elided());
+        let writer = {
+            let lock = lock.clone();
+            thread::spawn(move || *lock.write() = 1)
+        };
+        // readers keep getting in until the writer waits
+        while let Some(guard) = lock.try_read() {
+            thread::yield_now();
+            drop(guard);
+        }
+        drop(reader);
+        writer.join().unwrap();
+        assert_eq!(*lock.read(), 1);
+    }
+}
+
+/// Readers check that the two halves, always written together,
+/// match.
+#[test]
+fn readers_never_see_torn_writes() {
+    let lock = Arc::new(ElidedRwLock::new((0u64, 0u64)));
+    let writer = {
+        let lock = lock.clone();
+        thread::spawn(move || {
+            for i in 1..=20_000 {
+                let mut guard = lock.write();
+                guard.0 = i;
+                guard.1 = i;
+            }
+        })
+    };
+    let readers: Vec<_> = (0..3)
+        .map(|_| {
+            let lock = lock.clone();
+            thread::spawn(move || {
+                let mut last = 0;
+                for _ in 0..20_000 {
+                    let (a, b) = *lock.read();
+                    assert_eq!(a, b, "torn write");
+                    assert!(a >= last, "went back in time");
+                    last = a;
+                }
+            })
+        })
+        .collect();
+    writer.join().unwrap();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    assert_eq!(*lock.read(), (20_000, 20_000));
+}
@@ -0,0 +1,101 @@
+extern crate rtm;
+
+use std::sync::Arc;
+use std::thread;
+
+use rtm::TxSkipList;
+
+#[test]
+fn ordered_operations() {
+    let list = TxSkipList::with_capacity(100);
+    for key in (0..100u32).rev() {
+        assert_eq!(list.insert(key, key * 10), Ok(None));
+    }
+    assert_eq!(list.insert(7, 0), Ok(Some(70)));
+    assert_eq!(list.get(&7), Some(0));
+    assert_eq!(list.get(&100), None);
+    assert_eq!(list.insert(100, 0), Err((100, 0)));
+    assert_eq!(list.remove(&7), Some(0));
+    assert!(!list.contains_key(&7));
+    assert_eq!(list.insert(100, 1000), Ok(None));
+
+    let keys: Vec<u32> = list.range(..).into_iter().map(|(k, _)| k).collect();
+    let expected: Vec<u32> = (0..=100).filter(|&k| k != 7).collect();
+    assert_eq!(keys, expected);
+    assert_eq!(list.range(5..9), vec![(5, 50), (6, 60), (8, 80)]);
+    assert_eq!(
+        list.range(98..=100),
+        vec![(98, 980), (99, 990), (100, 1000)]
+    );
+    assert_eq!(list.range(200..), vec![]);
+}
+
+#[test]
+fn long_scans_are_chunked() {
+    let list = TxSkipList::with_capacity(1000);
+    for key in 0..1000u64 {
+        list.insert(key, !key).unwrap();
+    }
+    let from = std::ops::Bound::Excluded(10);
+    let entries = list.range((from, std::ops::Bound::Unbounded));
+    assert_eq!(entries.len(), 989);
+    assert!(entries
+        .iter()
+        .zip(11..)
+        .all(|(&(k, v), e)| k == e && v == !e));
+}
+
+#[cfg(all(feature = "emulated", feature = "fault-injection"))]
+#[test]
+fn capacity_aborts_halve_the_chunk() {
+    use rtm::inject::{self, Schedule};
+    use rtm::AbortCode;
+
+    let list = TxSkipList::with_capacity(40);
+    for key in 0..40u8 {
+        list.insert(key, ()).unwrap();
+    }
+    let guard = inject::install(Schedule::new());
+    assert_eq!(list.range(..).len(), 40);
+    assert_eq!(guard.attempts(), 2);
+    drop(guard);
+
+    let guard = inject::install(Schedule::new().nth(1, AbortCode::Capacity));
+    assert_eq!(list.range(..).len(), 40);
+    assert_eq!(guard.attempts(), 4);
+}
+
+/// Writers churn disjoint keys while a reader scans, every scan
+/// must come back sorted and hold the keys nobody removes.
+#[test]
+fn concurrent_updates_and_scans() {
+    let list = Arc::new(TxSkipList::with_capacity(4096));
+    for key in (0..4000u32).step_by(4) {
+        list.insert(key, key).unwrap();
+    }
+    let writers: Vec<_> = (1..4u32)
+        .map(|t| {
+            let list = list.clone();
+            thread::spawn(move || {
+                for round in 0..20 {
+                    for key in (t..4000).step_by(4) {
+                        if round % 2 == 0 {
+                            list.insert(key, key).unwrap();
+                        } else {
+                            assert_eq!(list.remove(&key), Some(key));
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+    for _ in 0..50 {
+        let entries = list.range(..);
+        assert!(entries.windows(2).all(|w| w[0].0 < w[1].0), "unsorted");
+        assert_eq!(entries.iter().filter(|&&(k, _)| k % 4 == 0).count(), 1000);
+    }
+    for writer in writers {
+        writer.join().unwrap();
+    }
+    assert_eq!(list.range(..).len(), 1000);
+}
@@ -0,0 +1,58 @@
+extern crate rtm;
+
+use std::sync::Arc;
+use std::thread;
+
+use rtm::TxStack;
+
+#[test]
+fn lifo() {
+    let stack = TxStack::with_capacity(2);
+    assert!(stack.is_empty());
+    assert_eq!(stack.push('a'), Ok(()));
+    assert_eq!(stack.push('b'), Ok(()));
+    assert_eq!(stack.push('c'), Err('c'));
+    assert_eq!(stack.len(), 2);
+    assert_eq!(stack.pop(), Some('b'));
+    assert_eq!(stack.pop(), Some('a'));
+    assert_eq!(stack.pop(), None);
+}
+
+#[test]
+fn batches() {
+    let stack = TxStack::with_capacity(4);
+    assert!(stack.push_many(&[1, 2, 3]));
+    assert!(!stack.push_many(&[4, 5]));
+    let mut out = [0; 8];
+    assert_eq!(stack.pop_many(&mut out), 3);
+    assert_eq!(out[..3], [3, 2, 1]);
+    assert_eq!(stack.pop_many(&mut out), 0);
+}
+
+/// Pairs pushed together are popped together.
+#[test]
+fn concurrent_batches() {
+    let stack = Arc::new(TxStack::with_capacity(32));
+    let threads: Vec<_> = (0..4)
+        .map(|t| {
+            let stack = stack.clone();
+            thread::spawn(move || {
+                let mut popped = 0;
+                for i in 0..10_000 {
+                    let value = t * 100_000 + i;
+                    if stack.push_many(&[value, value]) {
+                        let mut out = [0; 2];
+                        if stack.pop_many(&mut out) == 2 {
+                            assert_eq!(out[0], out[1], "batch was split");
+                            popped += 2;
+                        }
+                    }
+                }
+                popped
+            })
+        })
+        .collect();
+    let popped: usize = threads.into_iter().map(|t| t.join().unwrap()).sum();
+    assert_eq!(stack.len() % 2, 0);
+    assert!(popped > 0);
+}
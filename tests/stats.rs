@@ -0,0 +1,117 @@
+#![cfg(feature = "stats")]
+
+extern crate rtm;
+
+use std::thread;
+
+use rtm::stats::{self, Counts};
+use rtm::{Fallback, TxCell};
+
+/// Runs one transaction, returning the line of the call site.
+fn increment(data: &mut TxCell<u64>) -> u32 {
+    rtm::transaction(data, |d| unsafe { d.set(d.get() + 1) }, Fallback::Global).unwrap();
+    line!() - 1
+}
+
+/// Same as `increment`, from a call site of its own.
+fn increment_again(data: &mut TxCell<u64>) -> u32 {
+    rtm::transaction(data, |d| unsafe { d.set(d.get() + 1) }, Fallback::Global).unwrap();
+    line!() - 1
+}
+
+/// Critical sections run, whichever path ran them.
+fn runs(counts: &Counts) -> u64 {
+    counts.commits() + counts.fallbacks()
+}
+
+#[test]
+fn counts_per_site() {
+    let mut data = TxCell::new(0u64);
+    let line = (0..5).map(|_| increment_again(&mut data)).last().unwrap();
+    let counts = stats::thread_snapshot().site(file!(), line).unwrap();
+    assert_eq!(runs(&counts), 5);
+    assert_eq!(counts.attempts(), counts.commits() + counts.aborts());
+    if rtm::is_supported() {
+        assert_eq!(counts.commits(), 5);
+    } else {
+        assert_eq!(counts.fallbacks(), 5);
+    }
+    assert!(stats::thread_snapshot().site(file!(), line + 1).is_none());
+}
+
+#[test]
+fn exited_threads_are_counted() {
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            thread::spawn(|| {
+                let mut data = TxCell::new(0u64);
+                let mut line = 0;
+                for _ in 0..100 {
+                    line = increment(&mut data);
+                }
+                line
+            })
+        })
+        .collect();
+    let line = workers
+        .into_iter()
+        .map(|w| w.join().unwrap())
+        .last()
+        .unwrap();
+    let counts = stats::snapshot().site(file!(), line).unwrap();
+    // other tests may pass through the same site
+    assert!(runs(&counts) >= 400);
+
+    // the counters of an exited thread start from zero again
+    let reused = thread::spawn(|| {
+        let mut data = TxCell::new(0u64);
+        let line = increment(&mut data);
+        stats::thread_snapshot().site(file!(), line).unwrap()
+    });
+    assert_eq!(runs(&reused.join().unwrap()), 1);
+    let after = stats::snapshot().site(file!(), line).unwrap();
+    assert!(runs(&after) > runs(&counts));
+}
+
+#[test]
+fn thread_snapshot_is_thread_local() {
+    let line = thread::spawn(|| increment(&mut TxCell::new(0)))
+        .join()
+        .unwrap();
+    assert!(stats::thread_snapshot().site(file!(), line).is_none());
+    assert!(stats::snapshot().site(file!(), line).is_some());
+}
+
+#[cfg(feature = "fault-injection")]
+#[test]
+fn aborts_are_counted_by_flag_and_code() {
+    use rtm::inject::{self, Schedule};
+    use rtm::{AbortCode, AbortStatus};
+
+    fn attempt(data: &mut TxCell<u64>) -> u32 {
+        let _ = rtm::transaction_retry(data, |d| unsafe { d.set(1) }, 3, Fallback::None);
+        line!() - 1
+    }
+
+    let mut data = TxCell::new(0u64);
+    let _guard = inject::install(
+        Schedule::new()
+            .nth(1, AbortCode::Capacity)
+            .nth(2, AbortCode::from_code(42))
+            .nth(3, AbortCode::Retry)
+            .nth(4, AbortCode::Conflict),
+    );
+    let line = (0..3).map(|_| attempt(&mut data)).last().unwrap();
+    let counts = stats::thread_snapshot().site(file!(), line).unwrap();
+    assert_eq!(counts.aborts_with(AbortStatus::CAPACITY), 1);
+    assert_eq!(counts.aborts_with(AbortStatus::EXPLICIT), 1);
+    assert_eq!(counts.explicit_code(42), 1);
+    assert_eq!(counts.aborts_with(AbortStatus::RETRY), 1);
+    assert_eq!(counts.aborts_with(AbortStatus::CONFLICT), 1);
+    assert_eq!(
+        counts.aborts_with(AbortStatus::RETRY | AbortStatus::CONFLICT),
+        2
+    );
+    assert_eq!(counts.aborts(), 4);
+    assert_eq!(counts.fallbacks(), 0);
+}
@@ -0,0 +1,78 @@
+extern crate rtm;
+
+use rtm::{AbortCode, AbortStatus};
+
+#[test]
+fn combined_bits_are_all_reported() {
+    let status = AbortStatus::from_raw(6);
+    assert!(status.retry());
+    assert!(status.conflict());
+    assert!(!status.explicit());
+    assert!(!status.capacity());
+    assert_eq!(status.explicit_code(), None);
+    assert_eq!(status.raw(), 6);
+}
+
+#[test]
+fn explicit_code_survives_other_bits() {
+    let status = AbortStatus::from_raw((42 << 24) | 3);
+    assert!(status.explicit());
+    assert!(status.retry());
+    assert_eq!(status.explicit_code(), Some(42));
+    // the code byte means nothing without the explicit bit
+    assert_eq!(AbortStatus::from_raw(42 << 24).explicit_code(), None);
+    assert_eq!(AbortCode::Code42 as u32, (42 << 24) | 1);
+}
+
+#[test]
+fn lossy_code_priority() {
+    let all = AbortStatus::RETRY
+        | AbortStatus::CONFLICT
+        | AbortStatus::CAPACITY
+        | AbortStatus::DEBUG
+        | AbortStatus::NESTED;
+    let cases = [
+        (
+            all | AbortStatus::EXPLICIT | (7 << 24),
+            Some(AbortCode::Code7),
+        ),
+        (all, Some(AbortCode::Retry)),
+        (all & !AbortStatus::RETRY, Some(AbortCode::Conflict)),
+        (
+            AbortStatus::CAPACITY | AbortStatus::DEBUG | AbortStatus::NESTED,
+            Some(AbortCode::Capacity),
+        ),
+        (
+            AbortStatus::DEBUG | AbortStatus::NESTED,
+            Some(AbortCode::Debug),
+        ),
+        (AbortStatus::NESTED, Some(AbortCode::Nested)),
+        (0, None),
+    ];
+    for &(raw, code) in &cases {
+        assert_eq!(AbortStatus::from_raw(raw).code(), code, "status {:#x}", raw);
+    }
+}
+
+#[test]
+fn abort_code_round_trip() {
+    for &code in &[
+        AbortCode::Retry,
+        AbortCode::Conflict,
+        AbortCode::Capacity,
+        AbortCode::Debug,
+        AbortCode::Nested,
+    ] {
+        assert_eq!(AbortStatus::from(code).code(), Some(code));
+        assert_eq!(code.into_code(), None);
+    }
+    for code in 0..=255u8 {
+        let abort = AbortCode::from_code(code);
+        assert_eq!(abort.into_code(), Some(code));
+        let status = AbortStatus::from(abort);
+        assert_eq!(status, AbortStatus::from_code(code));
+        assert!(status.explicit());
+        assert_eq!(status.explicit_code(), Some(code));
+        assert_eq!(status.code(), Some(abort));
+    }
+}
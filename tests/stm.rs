@@ -0,0 +1,212 @@
+extern crate rtm;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use rtm::stm::{self, Tx};
+use rtm::{Fixed, TxCell};
+
+/// Keeps each cell on its own cache line, and so its own stripe.
+#[repr(align(64))]
+struct Padded(TxCell<u64>);
+
+#[test]
+fn read_own_writes() {
+    let cell = TxCell::new(1u64);
+    let out = stm::transaction(|tx| {
+        let value = tx.read(&cell)?;
+        tx.write(&cell, value + 1);
+        tx.read(&cell)
+    });
+    assert_eq!(out, Ok(2));
+    assert_eq!(unsafe { cell.get() }, 2);
+}
+
+#[test]
+fn explicit_abort_discards_writes() {
+    let cell = TxCell::new(1u64);
+    let out = stm::transaction(|tx| -> Result<(), _> {
+        tx.write(&cell, 5);
+        Err(tx.abort(9))
+    });
+    assert_eq!(out.unwrap_err().explicit_code(), Some(9));
+    assert_eq!(unsafe { cell.get() }, 1);
+}
+
+#[test]
+fn policy_can_give_up() {
+    let cell = TxCell::new(0u64);
+    let runs = AtomicUsize::new(0);
+    let out = stm::transaction_retry(
+        |tx: &mut Tx| -> Result<(), _> {
+            runs.fetch_add(1, Ordering::Relaxed);
+            tx.write(&cell, 1);
+            Err(tx.abort(1))
+        },
+        Fixed(3),
+    );
+    assert!(out.is_err());
+    assert_eq!(runs.load(Ordering::Relaxed), 1);
+    assert_eq!(unsafe { cell.get() }, 0);
+}
+
+/// Writers keep `a == b` at every commit. Readers assert it from
+/// inside the closure, so even a transaction that is going to
+/// abort must never see the two differ.
+#[test]
+fn opacity() {
+    let cells = Arc::new((Padded(TxCell::new(0)), Padded(TxCell::new(0))));
+    let mut threads = Vec::new();
+    for _ in 0..4 {
+        let cells = cells.clone();
+        threads.push(thread::spawn(move || {
+            for _ in 0..10_000 {
+                stm::transaction(|tx| {
+                    let a = tx.read(&(cells.0).0)?;
+                    let b = tx.read(&(cells.1).0)?;
+                    tx.write(&(cells.0).0, a + 1);
+                    tx.write(&(cells.1).0, b + 1);
+                    Ok(())
+                })
+                .unwrap();
+            }
+        }));
+    }
+    for _ in 0..4 {
+        let cells = cells.clone();
+        threads.push(thread::spawn(move || {
+            for _ in 0..10_000 {
+                stm::transaction(|tx| {
+                    let a = tx.read(&(cells.0).0)?;
+                    thread::yield_now();
+                    let b = tx.read(&(cells.1).0)?;
+                    assert_eq!(a, b, "inconsistent snapshot");
+                    Ok(())
+                })
+                .unwrap();
+            }
+        }));
+    }
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(unsafe { (cells.0).0.get() }, 40_000);
+    assert_eq!(unsafe { (cells.1).0.get() }, 40_000);
+}
+
+#[test]
+fn concurrent_disjoint_updates() {
+    const THREADS: usize = 8;
+    const ROUNDS: u64 = 20_000;
+    let cells: Arc<Vec<Padded>> = Arc::new((0..THREADS).map(|_| Padded(TxCell::new(0))).collect());
+    let threads: Vec<_> = (0..THREADS)
+        .map(|i| {
+            let cells = cells.clone();
+            thread::spawn(move || {
+                for _ in 0..ROUNDS {
+                    stm::transaction(|tx| {
+                        let value = tx.read(&cells[i].0)?;
+                        tx.write(&cells[i].0, value + 1);
+                        Ok(())
+                    })
+                    .unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    for cell in cells.iter() {
+        assert_eq!(unsafe { cell.0.get() }, ROUNDS);
+    }
+}
+
+/// Transfers between overlapping pairs of accounts keep the
+/// total constant.
+#[test]
+fn concurrent_transfers() {
+    const ACCOUNTS: usize = 16;
+    let accounts: Arc<Vec<Padded>> =
+        Arc::new((0..ACCOUNTS).map(|_| Padded(TxCell::new(100))).collect());
+    let threads: Vec<_> = (0..8)
+        .map(|seed| {
+            let accounts = accounts.clone();
+            thread::spawn(move || {
+                let mut state = seed as u64 * 7919 + 1;
+                for _ in 0..10_000 {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    let from = state as usize % ACCOUNTS;
+                    let to = (state >> 32) as usize % ACCOUNTS;
+                    stm::transaction(|tx| {
+                        let balance = tx.read(&accounts[from].0)?;
+                        if balance == 0 || from == to {
+                            return Ok(());
+                        }
+                        let other = tx.read(&accounts[to].0)?;
+                        tx.write(&accounts[from].0, balance - 1);
+                        tx.write(&accounts[to].0, other + 1);
+                        Ok(())
+                    })
+                    .unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let total: u64 = accounts
+        .iter()
+        .map(|account| unsafe { account.0.get() })
+        .sum();
+    assert_eq!(total, 100 * ACCOUNTS as u64);
+}
+
+/// Hardware and software transactions on the same cells. Each
+/// keeps `a == b` and checks it from inside the closure.
+#[test]
+fn hybrid_with_software() {
+    let cells = Arc::new((Padded(TxCell::new(0)), Padded(TxCell::new(0))));
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            let cells = cells.clone();
+            thread::spawn(move || {
+                for _ in 0..10_000 {
+                    let update = |tx: &mut Tx| {
+                        let a = tx.read(&(cells.0).0)?;
+                        let b = tx.read(&(cells.1).0)?;
+                        assert_eq!(a, b, "inconsistent snapshot");
+                        tx.write(&(cells.0).0, a + 1);
+                        tx.write(&(cells.1).0, b + 1);
+                        Ok(())
+                    };
+                    if i % 2 == 0 {
+                        stm::hybrid(update).unwrap();
+                    } else {
+                        stm::transaction(update).unwrap();
+                    }
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(unsafe { (cells.0).0.get() }, 80_000);
+    assert_eq!(unsafe { (cells.1).0.get() }, 80_000);
+}
+
+#[test]
+fn hybrid_explicit_abort() {
+    let cell = TxCell::new(1u64);
+    let out = stm::hybrid(|tx| -> Result<(), _> {
+        tx.write(&cell, 5);
+        Err(tx.abort(4))
+    });
+    assert_eq!(out.unwrap_err().explicit_code(), Some(4));
+    assert_eq!(unsafe { cell.get() }, 1);
+}
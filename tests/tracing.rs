@@ -0,0 +1,209 @@
+#![cfg(all(feature = "tracing", feature = "fault-injection"))]
+
+extern crate rtm;
+extern crate tracing;
+
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Level, Metadata, Subscriber};
+
+use rtm::inject::{self, Schedule};
+use rtm::{AbortCode, Fallback, TxCell};
+
+/// The rate limit is shared by every thread, tests counting
+/// events take turns.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn serial() -> MutexGuard<'static, ()> {
+    SERIAL.lock().unwrap_or_else(|poison| poison.into_inner())
+}
+
+#[derive(Clone, Debug)]
+struct Event {
+    level: Level,
+    fields: Vec<(String, String)>,
+    /// Innermost span entered when the event was emitted.
+    span: Option<u64>,
+}
+
+impl Event {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn message(&self) -> &str {
+        self.field("message").unwrap_or("")
+    }
+}
+
+#[derive(Default)]
+struct Log {
+    /// Name and fields of every span, by id less one.
+    spans: Vec<(String, Vec<(String, String)>)>,
+    events: Vec<Event>,
+    stack: Vec<u64>,
+}
+
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Log>>);
+
+impl Capture {
+    fn log(&self) -> MutexGuard<'_, Log> {
+        self.0.lock().unwrap()
+    }
+
+    fn events(&self, message: &str) -> Vec<Event> {
+        let log = self.log();
+        log.events
+            .iter()
+            .filter(|event| event.message() == message)
+            .cloned()
+            .collect()
+    }
+
+    fn span(&self, id: u64) -> (String, Vec<(String, String)>) {
+        self.log().spans[id as usize - 1].clone()
+    }
+}
+
+struct Fields<'a>(&'a mut Vec<(String, String)>);
+
+impl<'a> Visit for Fields<'a> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .push((field.name().to_string(), format!("{:?}", value)));
+    }
+}
+
+impl Subscriber for Capture {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Vec::new();
+        span.record(&mut Fields(&mut fields));
+        let mut log = self.log();
+        log.spans.push((span.metadata().name().to_string(), fields));
+        Id::from_u64(log.spans.len() as u64)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        let mut fields = Vec::new();
+        event.record(&mut Fields(&mut fields));
+        let mut log = self.log();
+        let span = log.stack.last().cloned();
+        log.events.push(Event {
+            level: *event.metadata().level(),
+            fields,
+            span,
+        });
+    }
+
+    fn enter(&self, span: &Id) {
+        self.log().stack.push(span.into_u64());
+    }
+
+    fn exit(&self, _: &Id) {
+        self.log().stack.pop();
+    }
+}
+
+const ABORTED: &str = "transaction aborted";
+const FELL_BACK: &str = "transaction fell back to lock";
+const OVERSIZED: &str = "transaction data spans more cache lines than the write set holds";
+const RATE_LIMITED: &str = "rate limited transaction events";
+
+#[test]
+fn aborts_are_reported_in_attempt_spans() {
+    let _serial = serial();
+    let capture = Capture::default();
+    tracing::subscriber::with_default(capture.clone(), || {
+        let mut data = TxCell::new(0u64);
+        let _guard = inject::install(
+            Schedule::new()
+                .nth(1, AbortCode::Retry)
+                .nth(2, AbortCode::Conflict),
+        );
+        let out = rtm::transaction_retry(&mut data, |d| unsafe { d.set(1) }, 3, Fallback::Global);
+        assert!(out.is_ok());
+    });
+
+    let aborts = capture.events(ABORTED);
+    assert_eq!(aborts.len(), 2);
+    for (attempt, event) in (1..).zip(aborts.iter()) {
+        assert_eq!(event.level, Level::DEBUG);
+        assert_eq!(event.field("attempt"), Some(attempt.to_string().as_str()));
+        let (name, fields) = capture.span(event.span.expect("abort outside a span"));
+        assert_eq!(name, "attempt");
+        assert!(fields.contains(&("attempt".to_string(), attempt.to_string())));
+    }
+    assert_ne!(aborts[0].span, aborts[1].span);
+
+    let fallbacks = capture.events(FELL_BACK);
+    assert_eq!(fallbacks.len(), 1);
+    assert_eq!(fallbacks[0].level, Level::INFO);
+    assert_eq!(fallbacks[0].span, None);
+    assert_eq!(fallbacks[0].field("code"), Some("Some(Conflict)"));
+}
+
+#[test]
+fn oversized_data_is_warned_once_per_site() {
+    let _serial = serial();
+    let capture = Capture::default();
+    tracing::subscriber::with_default(capture.clone(), || {
+        let mut big = Box::new([0u64; 8192]);
+        for _ in 0..3 {
+            rtm::transaction(&mut *big, |d| d[0] += 1, Fallback::Global).unwrap();
+        }
+        rtm::transaction(&mut *big, |d| d[1] += 1, Fallback::Global).unwrap();
+        let mut small = [0u64; 8];
+        rtm::transaction(&mut small, |d| d[0] += 1, Fallback::Global).unwrap();
+    });
+    let warnings = capture.events(OVERSIZED);
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings.iter().all(|event| event.level == Level::WARN));
+    assert_ne!(warnings[0].field("site"), warnings[1].field("site"));
+    let lines = rtm::cache_lines::<[u64; 8192]>().to_string();
+    assert_eq!(warnings[0].field("lines"), Some(lines.as_str()));
+    assert_eq!(warnings[0].field("data"), Some("\"[u64; 8192]\""));
+}
+
+#[test]
+fn events_are_rate_limited() {
+    let _serial = serial();
+    let capture = Capture::default();
+    let emitted = 300;
+    tracing::subscriber::with_default(capture.clone(), || {
+        let mut data = TxCell::new(0u64);
+        let _guard = inject::install(Schedule::new().random(1.0, 0, AbortCode::Conflict));
+        for _ in 0..emitted {
+            let _ = rtm::transaction(&mut data, |d| unsafe { d.set(1) }, Fallback::None);
+        }
+        // the drops are reported by the first event of a later second
+        thread::sleep(Duration::from_millis(1100));
+        let _ = rtm::transaction(&mut data, |d| unsafe { d.set(1) }, Fallback::None);
+    });
+    let reported = capture.events(ABORTED).len();
+    let dropped: usize = capture
+        .events(RATE_LIMITED)
+        .iter()
+        .map(|event| event.field("dropped").unwrap().parse::<usize>().unwrap())
+        .sum();
+    // at most 100 a second, other tests may have used some
+    assert!(dropped > 0);
+    assert!(reported < emitted);
+    assert_eq!(reported + dropped, emitted + 1);
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "std")]
+
+extern crate rtm;
+
+use std::panic::{self, AssertUnwindSafe};
+
+use rtm::{Fallback, TxCell, TxVec};
+
+#[test]
+fn abort_on_fallback_rolls_back() {
+    let mut data = TxCell::new(0u64);
+    let out = rtm::transaction(
+        &mut data,
+        |data| unsafe {
+            data.set(7);
+            rtm::abort(5);
+            data.set(8);
+        },
+        Fallback::Global,
+    );
+    let status = out.unwrap_err();
+    assert_eq!(status.explicit_code(), Some(5));
+    assert_eq!(*data.get_mut(), 0);
+}
+
+#[test]
+fn vec_is_restored() {
+    let mut vec = TxVec::from(vec![1u32, 2, 3]);
+    let out = rtm::transaction(
+        &mut vec,
+        |vec| unsafe {
+            vec.set(0, 10);
+            assert_eq!(vec.pop(), Some(3));
+            assert_eq!(vec.pop(), Some(2));
+            vec.push(20).unwrap();
+            vec.push(30).unwrap();
+            assert!(vec.is_full());
+            rtm::abort_with::<1>();
+        },
+        Fallback::Striped,
+    );
+    assert_eq!(out.unwrap_err().explicit_code(), Some(1));
+    assert_eq!(unsafe { vec.iter().collect::<Vec<_>>() }, vec![1, 2, 3]);
+}
+
+/// Logs a slot that was never written and a value with padding,
+/// `make miri` checks neither is read as initialized.
+#[test]
+fn push_then_abort() {
+    let mut vec = TxVec::with_capacity(2);
+    let out = rtm::transaction(
+        &mut vec,
+        |vec| unsafe {
+            vec.push((1u8, 2u64)).unwrap();
+            rtm::abort(3);
+        },
+        Fallback::Global,
+    );
+    assert_eq!(out.unwrap_err().explicit_code(), Some(3));
+    unsafe {
+        assert!(vec.is_empty());
+        vec.push((4, 5)).unwrap();
+        assert_eq!(vec.get(0), Some((4, 5)));
+    }
+}
+
+#[test]
+fn panic_on_fallback_rolls_back() {
+    let mut data = TxCell::new(0u64);
+    let out = panic::catch_unwind(AssertUnwindSafe(|| {
+        rtm::transaction(
+            &mut data,
+            |data| {
+                unsafe { data.set(1) };
+                panic!("rolled back");
+            },
+            Fallback::Global,
+        )
+    }));
+    assert!(out.is_err());
+    assert_eq!(*data.get_mut(), 0);
+}
+
+#[test]
+fn writes_are_kept_without_abort() {
+    let mut vec = TxVec::with_capacity(4);
+    for i in 0..4u8 {
+        let out = rtm::transaction(
+            &mut vec,
+            |vec| unsafe { vec.push(i).unwrap() },
+            Fallback::Global,
+        );
+        assert!(out.is_ok());
+    }
+    unsafe {
+        assert_eq!(vec.push(4), Err(4));
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,149 @@
+extern crate rtm;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Error {
+    Empty,
+    Full,
+    Other(u8),
+}
+
+impl From<Error> for u8 {
+    fn from(err: Error) -> u8 {
+        match err {
+            Error::Empty => 1,
+            Error::Full => 2,
+            Error::Other(code) => code,
+        }
+    }
+}
+
+impl From<u8> for Error {
+    fn from(code: u8) -> Error {
+        match code {
+            1 => Error::Empty,
+            2 => Error::Full,
+            code => Error::Other(code),
+        }
+    }
+}
+
+/// Either path is allowed here, whichever ran the result has to
+/// be consistent with the data.
+#[test]
+fn transaction_with_is_all_or_nothing() {
+    let mut data = 20u64;
+    match rtm::transaction_with(&mut data, |d| {
+        *d += 1;
+        *d * 2
+    }) {
+        Ok(value) => {
+            assert_eq!(value, 42);
+            assert_eq!(data, 21);
+        }
+        Err(status) => {
+            assert!(!status.explicit());
+            assert_eq!(data, 20);
+        }
+    }
+}
+
+#[cfg(feature = "emulated")]
+mod emulated {
+    use rtm::emulated::inject;
+    use rtm::{AbortStatus, TransactionError, TxCell};
+
+    use super::Error;
+
+    #[test]
+    fn transaction_with_returns_value() {
+        let mut data = TxCell::new(20u64);
+        let value = rtm::transaction_with(&mut data, |d| unsafe {
+            d.set(d.get() + 1);
+            d.get() * 2
+        });
+        assert_eq!(value, Ok(42));
+        assert_eq!(*data.get_mut(), 21);
+    }
+
+    #[test]
+    fn transaction_with_reports_abort() {
+        let mut data = TxCell::new(20u64);
+        let status = AbortStatus::from_raw(AbortStatus::CONFLICT);
+        inject(status);
+        let value = rtm::transaction_with(&mut data, |d| unsafe {
+            d.set(0);
+            "unreachable"
+        });
+        assert_eq!(value, Err(status));
+        assert_eq!(*data.get_mut(), 20);
+    }
+
+    #[test]
+    fn try_transaction_returns_value() {
+        let mut data = TxCell::new(3u32);
+        let out: Result<u32, TransactionError<Error>> =
+            rtm::try_transaction(&mut data, |d| unsafe {
+                d.set(d.get() * 3);
+                Ok(d.get() + 1)
+            });
+        assert_eq!(out, Ok(10));
+        assert_eq!(*data.get_mut(), 9);
+    }
+
+    #[test]
+    fn try_transaction_round_trips_errors() {
+        for err in [
+            Error::Empty,
+            Error::Full,
+            Error::Other(0),
+            Error::Other(200),
+        ] {
+            let mut data = TxCell::new(5u32);
+            let out: Result<(), _> = rtm::try_transaction(&mut data, |d| unsafe {
+                d.set(6);
+                Err(err)
+            });
+            assert_eq!(out, Err(TransactionError::Failed(err)));
+            assert_eq!(*data.get_mut(), 5);
+        }
+    }
+
+    #[test]
+    fn try_transaction_reports_abort() {
+        let mut data = TxCell::new(5u32);
+        let status = AbortStatus::from_raw(AbortStatus::CAPACITY);
+        inject(status);
+        let out: Result<u32, TransactionError<Error>> =
+            rtm::try_transaction(&mut data, |d| unsafe {
+                d.set(6);
+                Ok(1)
+            });
+        assert_eq!(out, Err(TransactionError::Aborted(status)));
+        assert_eq!(*data.get_mut(), 5);
+    }
+}
+
+#[cfg(not(feature = "emulated"))]
+#[test]
+fn try_transaction_error_is_all_or_nothing() {
+    use rtm::TransactionError;
+
+    let mut data = 5u32;
+    let out: Result<u32, TransactionError<Error>> = rtm::try_transaction(&mut data, |d| {
+        *d = 6;
+        Err(Error::Full)
+    });
+    match out {
+        Err(TransactionError::Failed(err)) => assert_eq!(err, Error::Full),
+        Err(TransactionError::Aborted(status)) => assert!(!status.explicit()),
+        Ok(_) => panic!("closure returned an error"),
+    }
+    assert_eq!(data, 5);
+}
+
+#[test]
+fn error_codes_round_trip() {
+    for code in 0..=255u8 {
+        assert_eq!(u8::from(Error::from(code)), code);
+    }
+}